@@ -0,0 +1,81 @@
+use std::str::FromStr;
+use std::time::Instant;
+
+use chess::Board;
+
+use crate::bots::basic_bot::BasicBot;
+use crate::bots::bot_traits::Search;
+use crate::bots::search_params::SearchParams;
+use crate::bots::time_manager::TimeManager;
+
+pub const DEFAULT_BENCH_DEPTH: u16 = 6;
+
+const BENCH_POSITIONS: [&str; 8] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+    "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "6k1/5ppp/8/8/8/8/q4PPP/3R2K1 b - - 0 1",
+    "8/8/4k3/3p4/3P4/4K3/8/8 w - - 0 1",
+];
+
+/**
+ * Searches every position with a fresh bot, returns the nodes searched in total.
+ */
+fn run(depth: u16, params: &SearchParams) -> u64 {
+    let mut total_nodes = 0;
+    for fen in BENCH_POSITIONS {
+        let board = Board::from_str(fen).expect("Bench positions are valid.");
+        let mut bot = BasicBot::new(&board, 16 * 1024 * 1024);
+        bot.uci.quiet = true;
+        bot.params = params.clone();
+
+        bot.search(depth, TimeManager::unlimited());
+        println!("{:>10} nodes  {}", bot.uci.nodes_total, fen);
+        total_nodes += bot.uci.nodes_total;
+    }
+    total_nodes
+}
+
+/**
+ * `alice-engine bench [depth]`: searches a fixed set of positions to a fixed depth,
+ * adding one kind of pruning at a time, to compare the size of the trees.
+ */
+pub fn bench(depth: u16) {
+    let none = SearchParams {
+        null_move_pruning: false,
+        late_move_reductions: false,
+        late_move_pruning: false,
+        reverse_futility_pruning: false,
+        futility_pruning: false,
+        razoring: false,
+        ..SearchParams::default()
+    };
+    let null_move = SearchParams {
+        null_move_pruning: true,
+        ..none.clone()
+    };
+    let configs = [
+        ("no pruning", none.clone()),
+        ("null move pruning", null_move),
+        ("everything", SearchParams::default()),
+    ];
+
+    let mut baseline = None;
+    for (name, params) in configs {
+        println!("{}:", name);
+        let start = Instant::now();
+        let nodes = run(depth, &params);
+        let ms = start.elapsed().as_millis().max(1) as u64;
+        let baseline = *baseline.get_or_insert(nodes);
+        println!(
+            "{} nodes ({:.1}% fewer), {} ms, {} nps\n",
+            nodes,
+            100.0 - nodes as f64 * 100.0 / baseline.max(1) as f64,
+            ms,
+            nodes * 1000 / ms
+        );
+    }
+}
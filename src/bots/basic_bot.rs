@@ -1,37 +1,320 @@
 use std::cmp;
+use std::mem;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
 
+use crate::tables::history::{previous_piece_to, History};
 use crate::tables::piece_sq_tables::{create_pesto_piece_sqaure, ColoredTables};
-use crate::tables::zobrist::{NodeInfo, ZobristHashMap};
+use crate::tables::transposition::{Bound, TTEntry, TranspositionTable};
+use crate::tables::zobrist::ZobristValues;
+use crate::types::score;
 use crate::uci::uci::Uci;
-use crate::{bots::bot_traits::Evaluation, moves::move_gen::generate_moves};
+use crate::bots::bot_traits::BoardEvaluator;
+use crate::bots::search_limits::SearchLimits;
+use crate::bots::search_params::{SearchParams, MAX_REDUCED_MOVES};
+use crate::bots::skill::Skill;
+use crate::bots::time_manager::TimeManager;
+use crate::moves::move_picker::{is_quiet, MovePicker};
+use crate::moves::see::see;
+use crate::bots::bot_traits::Evaluation;
+use crate::bots::bot_traits::Search;
 
-use chess::{Board, ChessMove, Piece};
+use chess::{Board, BoardStatus, ChessMove, MoveGen, Piece, EMPTY};
+
+/**
+ * Deepest iteration the search will go to, also the size of the per-depth tables.
+ */
+pub const MAX_DEPTH: u16 = 64;
+
+/**
+ * Furthest a line can go from the root, extensions make lines longer than the depth.
+ */
+pub const MAX_PLY: usize = MAX_DEPTH as usize * 2;
+
+/**
+ * How many quiet moves of a node are remembered to be punished in the history.
+ */
+const MAX_QUIETS_SEARCHED: usize = 64;
 
 pub struct BasicBot {
     pub board: Board,
     pub uci: Uci,
     pub pesto: (ColoredTables, ColoredTables),
     pub killer_moves: Vec<Vec<Option<ChessMove>>>,
-    tt_table: ZobristHashMap<NodeInfo>,
-    in_search_killer_moves: Vec<ChessMove>,
-    in_search_normal_moves: Vec<ChessMove>,
+    /**
+     * Triangular pv table, `pv_table[ply]` is the best line found from the node at that ply.
+     */
+    pub pv_table: Vec<Vec<ChessMove>>,
+    /**
+     * Shared with the helper threads.
+     */
+    pub tt_table: Arc<TranspositionTable>,
+    pub zobrist: ZobristValues,
+    /**
+     * Zobrist keys of every position of the game up to the root, followed by the positions
+     * from the root to the node being searched (updated incrementally on every move).
+     */
+    pub position_keys: Vec<u64>,
+    /**
+     * The halfmove clock (plies since the last capture or pawn move) of every position in `position_keys`.
+     */
+    pub halfmove_clocks: Vec<u16>,
+    /**
+     * How many entries of `position_keys` are from the game, the last one of them is the root.
+     */
+    game_length: usize,
+    /**
+     * How much we dislike draws in centipawns, a draw scores `-contempt` for the side we search for.
+     * The UCI "Contempt" option, negative to play for a draw.
+     */
+    pub contempt: i32,
+    pub params: SearchParams,
+    /**
+     * Null moves aren't tried before this ply, set while verifying a null-move cutoff.
+     */
+    null_move_min_ply: usize,
+    /**
+     * How many plies the line to every ply was extended by, capped by `params.max_line_extensions`.
+     */
+    line_extensions: Vec<u16>,
+    /**
+     * Whether the move to every ply was a quiet move that hangs material (by SEE).
+     */
+    sacrifices: Vec<bool>,
+    /**
+     * The move left out of the search of the node at that ply, while checking whether
+     * the hash move is singular.
+     */
+    excluded_moves: Vec<Option<ChessMove>>,
+    /**
+     * How many of the best root moves are searched and reported, the UCI "MultiPV" option.
+     */
+    pub multi_pv: usize,
+    /**
+     * How many threads search at once, the UCI "Threads" option.
+     */
+    pub threads: usize,
+    /**
+     * 0 for the bot that reports the search, 1 and up for its helpers.
+     */
+    pub thread_index: usize,
+    /**
+     * The bots of the other threads, kept between searches so their tables are only allocated once.
+     */
+    helpers: Vec<BasicBot>,
+    /**
+     * The helpers' "stop", set when this bot's search is done.
+     */
+    helper_stop: Arc<AtomicBool>,
+    /**
+     * Nodes searched by the helpers, they add to it every 1024 nodes.
+     */
+    helper_nodes: Arc<AtomicU64>,
+    /**
+     * Root moves left out of the search, the best moves of the lines
+     * already found in the current iteration.
+     */
+    pub root_excluded_moves: Vec<ChessMove>,
+    /**
+     * The "go searchmoves", "nodes" and "mate" limits of the next search.
+     */
+    pub limits: SearchLimits,
+    /**
+     * The "Skill Level", "UCI_LimitStrength", "UCI_Elo" and "Seed" options, for playing weaker.
+     */
+    pub skill: Skill,
+    /**
+     * `params.reduction_table()`, rebuilt at the start of every search.
+     */
+    pub reduction_table: Vec<Vec<u16>>,
+    pub history: History,
+    /**
+     * Set by the UCI "stop" command, shared with the thread reading stdin.
+     */
+    pub stop: Arc<AtomicBool>,
+    /**
+     * The UCI "Ponder" option, whether the GUI lets us think on the opponent's time.
+     */
+    pub ponder: bool,
+    /**
+     * Set while a "go ponder" search runs, cleared by "ponderhit". Shared like `stop`.
+     */
+    pub pondering: Arc<AtomicBool>,
+    pub time_manager: TimeManager,
+    search_aborted: bool,
+    /**
+     * How many reduced moves looked better than expected and were searched again
+     * to the full depth, in the current search.
+     */
+    reduced_researches: u64,
 }
 
 impl BasicBot {
     pub fn new(board: &Board, tt_byte_size: usize) -> Self {
-        Self {
-            board: board.clone(),
+        let mut bot = Self {
+            board: *board,
             pesto: create_pesto_piece_sqaure(),
             uci: Uci::default(),
-            killer_moves: vec![vec![None; 4]; 15],
-            tt_table: ZobristHashMap::new(tt_byte_size),
-            in_search_killer_moves: Vec::new(),
-            in_search_normal_moves: Vec::new(),
-        }
+            killer_moves: vec![vec![None; 4]; MAX_DEPTH as usize + 1],
+            pv_table: (0..=MAX_PLY).map(|_| Vec::with_capacity(MAX_PLY)).collect(),
+            tt_table: Arc::new(TranspositionTable::new(tt_byte_size)),
+            zobrist: ZobristValues::default(),
+            position_keys: Vec::new(),
+            halfmove_clocks: Vec::new(),
+            game_length: 0,
+            contempt: 0,
+            params: SearchParams::default(),
+            null_move_min_ply: 0,
+            line_extensions: vec![0; MAX_PLY + 1],
+            sacrifices: vec![false; MAX_PLY + 1],
+            excluded_moves: vec![None; MAX_PLY + 1],
+            multi_pv: 1,
+            threads: 1,
+            thread_index: 0,
+            helpers: Vec::new(),
+            helper_stop: Arc::new(AtomicBool::new(false)),
+            helper_nodes: Arc::new(AtomicU64::new(0)),
+            root_excluded_moves: Vec::new(),
+            limits: SearchLimits::default(),
+            skill: Skill::default(),
+            reduction_table: SearchParams::default().reduction_table(),
+            history: History::default(),
+            stop: Arc::new(AtomicBool::new(false)),
+            ponder: false,
+            pondering: Arc::new(AtomicBool::new(false)),
+            time_manager: TimeManager::unlimited(),
+            search_aborted: false,
+            reduced_researches: 0,
+        };
+        bot.change_board(board);
+        bot
     }
 
+    /**
+     * Starts a new game history from `board`.
+     */
     pub fn change_board(&mut self, board: &Board) {
-        self.board = board.clone();
+        self.set_position(board, 0, &[]);
+    }
+
+    /**
+     * Sets up the position of the UCI "position" command, keeping every position on the way
+     * so the search can tell repetitions apart.
+     * Illegal moves are skipped.
+     */
+    pub fn set_position(&mut self, board: &Board, halfmove_clock: u16, moves: &[ChessMove]) {
+        self.board = *board;
+        self.position_keys.clear();
+        self.halfmove_clocks.clear();
+        self.position_keys.push(self.zobrist.hash(board));
+        self.halfmove_clocks.push(halfmove_clock);
+
+        for chess_move in moves {
+            if self.board.status() != BoardStatus::Ongoing || !self.board.legal(*chess_move) {
+                continue;
+            }
+            let board = self.board;
+            let new_board = board.make_move_new(*chess_move);
+            self.push_position(&board, *chess_move, &new_board);
+            self.board = new_board;
+        }
+        self.game_length = self.position_keys.len();
+    }
+
+    /**
+     * Forgets everything learned from earlier games for the UCI "ucinewgame": the transposition table,
+     * the killers, the history and the moves played. Back to the starting position until the next "position".
+     */
+    pub fn new_game(&mut self) {
+        self.tt_table.clear();
+        self.history.clear();
+        self.reset();
+        self.limits = SearchLimits::default();
+        self.set_position(&Board::default(), 0, &[]);
+        for helper in self.helpers.iter_mut() {
+            helper.history.clear();
+            helper.reset();
+        }
+    }
+
+    /**
+     * Records the position reached by `chess_move`.
+     */
+    fn push_position(&mut self, board: &Board, chess_move: ChessMove, new_board: &Board) {
+        let key = *self.position_keys.last().unwrap();
+        let halfmove_clock = *self.halfmove_clocks.last().unwrap();
+        let irreversible = board.piece_on(chess_move.get_source()) == Some(Piece::Pawn)
+            || board.piece_on(chess_move.get_dest()).is_some();
+
+        self.position_keys
+            .push(self.zobrist.update_hash(key, board, chess_move, new_board));
+        self.halfmove_clocks
+            .push(if irreversible { 0 } else { halfmove_clock + 1 });
+    }
+
+    /**
+     * Records the position after passing the turn. Nothing before it can be repeated
+     * in the search below it, so the clock starts over.
+     */
+    fn push_null_position(&mut self, board: &Board, new_board: &Board) {
+        let key = *self.position_keys.last().unwrap();
+        self.position_keys
+            .push(self.zobrist.update_null_move(key, board, new_board));
+        self.halfmove_clocks.push(0);
+    }
+
+    fn pop_position(&mut self) {
+        self.position_keys.pop();
+        self.halfmove_clocks.pop();
+    }
+
+    /**
+     * Throws away the positions of the last search, leaving the game up to the root.
+     */
+    pub fn truncate_to_root(&mut self) {
+        self.position_keys.truncate(self.game_length);
+        self.halfmove_clocks.truncate(self.game_length);
+    }
+
+    /**
+     * Whether the current position already happened since the last irreversible move.
+     *
+     * Repeating a position inside the search counts as a draw right away (whoever
+     * repeated it could do it again), a position from before the root has to be repeated twice.
+     */
+    pub fn is_repetition(&self) -> bool {
+        let last = self.position_keys.len() - 1;
+        let key = self.position_keys[last];
+        let oldest = last.saturating_sub(self.halfmove_clocks[last] as usize);
+
+        let mut repetitions = 0;
+        // positions with the same side to move are two plies apart.
+        let mut index = last;
+        while index >= oldest + 2 {
+            index -= 2;
+            if self.position_keys[index] == key {
+                if index >= self.game_length {
+                    return true;
+                }
+                repetitions += 1;
+                if repetitions >= 2 {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /**
+     * Draws are scored with contempt from the root's side to move point of view.
+     */
+    pub fn draw_score(&self, ply: usize) -> i32 {
+        if ply.is_multiple_of(2) {
+            score::DRAW - self.contempt
+        } else {
+            score::DRAW + self.contempt
+        }
     }
 
     pub fn reset(&mut self) {
@@ -40,23 +323,150 @@ impl BasicBot {
                 self.killer_moves[i][j] = None;
             }
         }
-        self.in_search_normal_moves.clear();
-        self.in_search_killer_moves.clear();
+        self.clear_search();
+    }
+
+    /**
+     * Forgets what the last search found and counted, before the next one starts.
+     * Its iterations, lines and abort would otherwise leak into the new search.
+     */
+    pub fn clear_search(&mut self) {
         self.uci.depth_data.clear();
+        self.uci.lines.clear();
         self.uci.nodes_total = 0;
         self.uci.ms_passed = 0;
-    }
-    pub fn reset_in_search_vectors(&mut self) {
-        self.in_search_killer_moves.clear();
-        self.in_search_normal_moves.clear();
+        self.search_aborted = false;
+        self.reduced_researches = 0;
+        self.null_move_min_ply = 0;
     }
 
     /**
-     * * Calculates the pieces in the board.
+     * Whether the running search has to give up, because of "stop", the node limit or the hard time limit.
      *
-     * (white_mg_score, white_eg_score, black_mg_score, black_eg_score) is the return type.
-     * im sorry for using tuples again.
+     * The first iteration is never aborted so there's always a move to play.
+     * Once it returns true, it keeps returning true until the next search.
+     */
+    pub fn should_abort(&mut self) -> bool {
+        if self.search_aborted {
+            return true;
+        }
+        if self.uci.depth_data.is_empty() {
+            return false;
+        }
+        // checking the clock every node is too slow
+        if self.uci.nodes_total & 1023 == 0 {
+            self.time_manager.check_ponderhit();
+        }
+        if self.stop.load(Ordering::Relaxed)
+            || self.limits.nodes_reached(self.nodes())
+            || (self.uci.nodes_total & 1023 == 0 && self.time_manager.hard_limit_reached())
+        {
+            self.search_aborted = true;
+        }
+        self.search_aborted
+    }
+
+    fn count_node(&mut self) {
+        self.uci.nodes_total += 1;
+        if self.thread_index > 0 && self.uci.nodes_total & 1023 == 0 {
+            self.helper_nodes.fetch_add(1024, Ordering::Relaxed);
+        }
+    }
+
+    /**
+     * Nodes searched so far by every thread.
+     */
+    pub fn nodes(&self) -> u64 {
+        if self.thread_index == 0 {
+            self.uci.nodes_total + self.helper_nodes.load(Ordering::Relaxed)
+        } else {
+            self.uci.nodes_total
+        }
+    }
+
+    /**
+     * Lazy SMP: runs `search` on this bot while `threads - 1` helpers search the same root.
+     * The helpers share nothing but the transposition table, and what they store there
+     * makes this bot's search faster. Only this bot's result is reported.
+     */
+    pub fn with_helpers<T>(&mut self, search: impl FnOnce(&mut Self) -> T) -> T {
+        if self.thread_index > 0 {
+            return search(self);
+        }
+        self.prepare_helpers();
+        let mut helpers = mem::take(&mut self.helpers);
+
+        let result = thread::scope(|scope| {
+            for helper in helpers.iter_mut() {
+                scope.spawn(move || helper.search(MAX_DEPTH, TimeManager::unlimited()));
+            }
+            let result = search(self);
+            self.helper_stop.store(true, Ordering::Relaxed);
+            result
+        });
+        self.helpers = helpers;
+        result
+    }
+
+    /**
+     * Gives every helper this bot's root and settings.
      */
+    fn prepare_helpers(&mut self) {
+        let count = self.threads.max(1) - 1;
+        self.helpers.truncate(count);
+        while self.helpers.len() < count {
+            let mut helper = BasicBot::new(&self.board, 0);
+            helper.thread_index = self.helpers.len() + 1;
+            helper.zobrist = self.zobrist.clone();
+            helper.uci.quiet = true;
+            helper.stop = Arc::clone(&self.helper_stop);
+            helper.helper_nodes = Arc::clone(&self.helper_nodes);
+            self.helpers.push(helper);
+        }
+        self.helper_stop.store(false, Ordering::Relaxed);
+        self.helper_nodes.store(0, Ordering::Relaxed);
+
+        for helper in self.helpers.iter_mut() {
+            helper.reset();
+            helper.board = self.board;
+            helper.position_keys.clone_from(&self.position_keys);
+            helper.halfmove_clocks.clone_from(&self.halfmove_clocks);
+            helper.game_length = self.game_length;
+            helper.tt_table = Arc::clone(&self.tt_table);
+            helper.params = self.params.clone();
+            helper.contempt = self.contempt;
+            helper.skill = self.skill.clone();
+            helper.limits = SearchLimits {
+                search_moves: self.limits.search_moves.clone(),
+                ..SearchLimits::default()
+            };
+        }
+    }
+
+    /**
+     * How many moves the current node is away from the root.
+     */
+    pub fn ply(&self) -> usize {
+        self.position_keys.len() - self.game_length
+    }
+
+    /**
+     * Whether `chess_move` is searched at the root: one of the "searchmoves" and not the best move of an earlier line.
+     */
+    pub fn is_root_move(&self, chess_move: ChessMove) -> bool {
+        self.limits.is_root_move(chess_move) && !self.root_excluded_moves.contains(&chess_move)
+    }
+
+    /**
+     * Makes `chess_move` followed by the pv of the next ply the pv of `ply`.
+     */
+    fn update_pv(&mut self, ply: usize, chess_move: ChessMove) {
+        let (current, rest) = self.pv_table.split_at_mut(ply + 1);
+        let pv = &mut current[ply];
+        pv.clear();
+        pv.push(chess_move);
+        pv.extend_from_slice(&rest[0]);
+    }
 
     pub fn piece_to_int(&self, p: Piece) -> u8 {
         match p {
@@ -69,160 +479,512 @@ impl BasicBot {
         }
     }
 
+    /**
+     * Alpha-beta in its negamax form: every score is relative to the side to move,
+     * a child's score is negated (and its window flipped) to get the parent's.
+     */
     pub fn internal_search(
         &mut self,
         board: &Board,
         max_depth: u16,
         depth: u16,
         mut alpha: i32,
-        mut beta: i32,
-        is_maximizing_player: bool,
-        _previous_move: Option<ChessMove>,
+        beta: i32,
+        previous_move: Option<ChessMove>,
     ) -> (i32, Option<ChessMove>) {
-        let all_moves = generate_moves(&board);
-
-        for board_move in all_moves {
-            if let Some(stored_killer_moves) = self.killer_moves.get(depth as usize) {
-                if stored_killer_moves.contains(&Some(board_move)) {
-                    self.in_search_killer_moves.push(board_move);
-                } else {
-                    self.in_search_normal_moves.push(board_move);
+        self.count_node();
+        if self.should_abort() {
+            return (0, None);
+        }
+
+        let key = *self
+            .position_keys
+            .last()
+            .expect("The root key is pushed before searching.");
+        let ply = self.ply();
+        self.pv_table[ply].clear();
+        if ply >= MAX_PLY - 1 {
+            return (self.static_evaluation(board), None);
+        }
+        let excluded_move = self.excluded_moves[ply];
+
+        if ply > 0 {
+            if self.is_repetition() || self.is_insufficient_material(board) {
+                return (self.draw_score(ply), None);
+            }
+            // the fifty-move rule, unless the last move was checkmate.
+            if *self.halfmove_clocks.last().unwrap() >= 100
+                && (*board.checkers() == EMPTY || MoveGen::new_legal(board).len() > 0)
+            {
+                return (self.draw_score(ply), None);
+            }
+        }
+
+        let tt_entry = self.tt_table.probe(key);
+        let pv_node = beta - alpha > 1;
+
+        // the entry is about the position with every move, not without the excluded one.
+        // pv nodes are searched anyway, a cutoff there would cut the pv short.
+        if depth > 0 && ply > 0 && excluded_move.is_none() && !pv_node {
+            if let Some(entry) = tt_entry.filter(|entry| entry.depth >= depth) {
+                let score = score::score_from_tt(entry.score, ply);
+                // a score from a search with another window is only usable
+                // if its bound proves it's outside of ours.
+                let usable = match entry.bound {
+                    Bound::Exact => true,
+                    Bound::Lower => score >= beta,
+                    Bound::Upper => score <= alpha,
+                };
+                if usable {
+                    return (score, entry.best_move);
                 }
             }
         }
 
-        let sorted_moves: Vec<ChessMove> = self.in_search_killer_moves.iter()
-            .chain(self.in_search_normal_moves.iter())
-            .map(|f| f.clone())
-            .collect();
+        let in_check = *board.checkers() != EMPTY;
+        // there's no standing pat in check, the static evaluation doesn't mean much there.
+        let static_eval = (!in_check).then(|| self.static_evaluation(board));
 
-        self.reset_in_search_vectors();
+        // right after a check (or the only reply to one) the line is forcing, a quiet check
+        // could mate next, which the quiescence search behind razoring doesn't see.
+        // so are the two plies after a sacrifice: it threatens more than the static evaluation
+        // knows (the next move of its side shows what), or it's a blunder that the capture
+        // (ordered first) refutes right away.
+        let forcing = ply > 0
+            && (self.line_extensions[ply] > self.line_extensions[ply - 1]
+                || self.sacrifices[ply]
+                || self.sacrifices[ply - 1]);
+        if excluded_move.is_none() && !pv_node && ply > 0 && !forcing {
+            if let Some(score) =
+                static_eval.and_then(|eval| self.shallow_pruning(board, depth, alpha, beta, eval))
+            {
+                return (score, None);
+            }
+        }
+        if excluded_move.is_none() && !forcing {
+            if let Some(score) =
+                self.null_move_pruning(board, max_depth, depth, beta, static_eval, previous_move)
+            {
+                return (score, None);
+            }
+        }
 
         if depth == 0 {
-            let evaluation = self.evaluation(board, &sorted_moves, is_maximizing_player);
-            return (evaluation, None);
-        }
-
-        let mut best_move = sorted_moves.get(0).map(|f| f.clone()); // Store the first move as the best move initially
-
-        if is_maximizing_player {
-            let mut best_val = -1000000;
-
-            for board_move in sorted_moves.iter() {
-                let board = board.make_move_new(*board_move);
-
-                let node_info = if self.tt_table.contains(&board) {
-                    let node_info = self.tt_table.get(&board).unwrap();
-                    // im assuming the deeper it is, the lower the depth is
-                    if node_info.depth < depth {
-                        let (eval, _) = self.internal_search(
-                            &board,
-                            max_depth,
-                            depth - 1,
-                            alpha,
-                            beta,
-                            !is_maximizing_player,
-                            Some(*board_move),
-                        );
-                        NodeInfo { eval, best_move, depth }
-                    } else {
-                        node_info.clone() // here's to hoping this function won't get called.
-                    }
-                } else {
-                    let (eval, _) = self.internal_search(
-                        &board,
+            // checkmate or stalemate
+            if MoveGen::new_legal(board).len() == 0 {
+                return (self.evaluation(board, &[], ply), None);
+            }
+            return (self.quiescence_search(board, ply, alpha, beta), None);
+        }
+
+        // the best move found by an earlier search of this position is searched first,
+        // so the window it produces can cut the rest of the moves.
+        // at the root it's the best move of the same line in the previous iteration.
+        let previous_line_move = if ply == 0 {
+            self.uci
+                .lines
+                .get(self.root_excluded_moves.len())
+                .and_then(|line| line.best_move)
+        } else {
+            None
+        };
+        let hash_move = previous_line_move.or_else(|| tt_entry.and_then(|entry| entry.best_move));
+        let previous = previous_move.and_then(|previous_move| previous_piece_to(board, previous_move));
+        let mut move_picker = MovePicker::new(
+            board,
+            hash_move,
+            &self.killer_moves[depth as usize],
+            self.history.countermove(previous),
+            previous,
+        );
+
+        let original_alpha = alpha;
+        let mut best_move = None;
+        let mut best_val = -score::INFINITY;
+        // with a single legal reply to a check, the position is forcing enough to look deeper.
+        let one_reply = in_check && MoveGen::new_legal(board).len() == 1;
+        let mut late_moves_searched = 0;
+        // for the history, every quiet move searched before a cutoff didn't cause it.
+        let mut quiets_searched = [ChessMove::default(); MAX_QUIETS_SEARCHED];
+        let mut quiet_count = 0;
+
+        let mut move_count = 0;
+        while let Some(board_move) = move_picker.next_move(board, &self.history) {
+            if Some(board_move) == excluded_move
+                || (ply == 0 && !self.is_root_move(board_move))
+            {
+                continue;
+            }
+            let index = move_count;
+            move_count += 1;
+            let new_board = board.make_move_new(board_move);
+
+            // quiet moves ordered after the hash move and the killers are unlikely to be best.
+            let late_quiet_move = !in_check
+                && Some(board_move) != hash_move
+                && is_quiet(board, board_move)
+                && *new_board.checkers() == EMPTY
+                && !self.killer_moves[depth as usize].contains(&Some(board_move));
+
+            // late move pruning, once a move that doesn't get us mated was found.
+            if late_quiet_move
+                && self.params.late_move_pruning
+                && ply > 0
+                && depth <= self.params.lmp_max_depth
+                && best_val > -score::MATE_BOUND
+                && late_moves_searched >= self.params.late_move_count(depth)
+            {
+                continue;
+            }
+            // futility pruning: a quiet move won't make up for being this far below alpha.
+            if late_quiet_move
+                && self.params.futility_pruning
+                && !pv_node
+                && depth <= self.params.futility_max_depth
+                && best_val > -score::MATE_BOUND
+                && !score::is_mate_score(alpha)
+                && static_eval.is_some_and(|eval| eval + self.params.futility_margin(depth) <= alpha)
+            {
+                continue;
+            }
+            if late_quiet_move {
+                late_moves_searched += 1;
+            }
+            let quiet = is_quiet(board, board_move);
+            if quiet && quiet_count < MAX_QUIETS_SEARCHED {
+                quiets_searched[quiet_count] = board_move;
+                quiet_count += 1;
+            }
+
+            let reduction = if late_quiet_move
+                && self.params.late_move_reductions
+                && depth >= self.params.lmr_min_depth
+                && index >= self.params.lmr_min_move_index
+            {
+                let reduction = self.reduction_table[depth as usize][index.min(MAX_REDUCED_MOVES - 1)];
+                // the lines we expect to be played are reduced less.
+                reduction.saturating_sub(pv_node as u16).min(depth.saturating_sub(2))
+            } else {
+                0
+            };
+
+            let gives_check = *new_board.checkers() != EMPTY;
+            let extend = self.line_extensions[ply] < self.params.max_line_extensions
+                && ((self.params.check_extensions && (gives_check || one_reply))
+                    || self.is_singular(board, max_depth, depth, board_move, tt_entry, previous_move));
+            let extension = extend as u16;
+            if self.search_aborted {
+                return (0, None);
+            }
+            let new_depth = depth - 1 + extension;
+            self.line_extensions[ply + 1] = self.line_extensions[ply] + extension;
+            self.sacrifices[ply + 1] = quiet && see(board, board_move) < 0;
+
+            self.push_position(board, board_move, &new_board);
+
+            // principal variation search: the first move is expected to be the best one,
+            // the others are only searched with a null window to prove they aren't better.
+            let mut eval = -score::INFINITY;
+            if index > 0 {
+                let (null_window_eval, _) = self.internal_search(
+                    &new_board,
+                    max_depth,
+                    new_depth - reduction,
+                    -alpha - 1,
+                    -alpha,
+                    Some(board_move),
+                );
+                eval = -null_window_eval;
+
+                // the reduced search says it might be better than expected, try the full depth.
+                if reduction > 0 && eval > alpha && !self.search_aborted {
+                    self.reduced_researches += 1;
+                    let (null_window_eval, _) = self.internal_search(
+                        &new_board,
                         max_depth,
-                        depth - 1,
-                        alpha,
-                        beta,
-                        !is_maximizing_player,
-                        Some(*board_move),
+                        new_depth,
+                        -alpha - 1,
+                        -alpha,
+                        Some(board_move),
                     );
-                    NodeInfo { eval, best_move, depth }
-                };
+                    eval = -null_window_eval;
+                }
+            }
+            // the first move, or a move that beat alpha in a pv node: its exact score is needed.
+            if index == 0 || (pv_node && eval > alpha && !self.search_aborted) {
+                let (full_eval, _) = self.internal_search(
+                    &new_board,
+                    max_depth,
+                    new_depth,
+                    -beta,
+                    -alpha,
+                    Some(board_move),
+                );
+                eval = -full_eval;
+            }
+            self.pop_position();
+
+            if self.search_aborted {
+                return (0, None);
+            }
+
+            if eval > best_val {
+                best_val = eval;
+                best_move = Some(board_move);
+                self.update_pv(ply, board_move);
+            }
+            alpha = cmp::max(alpha, best_val);
 
-                if node_info.eval > best_val {
-                    best_val = node_info.eval;
-                    best_move = Some(*board_move);
-
-                    self.tt_table.insert(
-                        &board,
-                        NodeInfo {
-                            eval: node_info.eval,
-                            best_move,
-                            depth
-                        },
+            if alpha >= beta {
+                // captures are ordered well enough by SEE and MVV-LVA.
+                if quiet {
+                    self.update_killer_move(depth, board_move);
+                    self.history.update(
+                        board,
+                        previous,
+                        board_move,
+                        &quiets_searched[..quiet_count],
+                        depth,
                     );
-                    self.uci.update_depth_data(depth, max_depth, best_move);
                 }
-                alpha = cmp::max(alpha, best_val);
+                break;
+            }
+        }
 
-                if beta <= alpha {
-                    self.update_killer_move(depth, *board_move);
-                    break;
-                }
+        if move_count == 0 {
+            // the only move was left out, the node fails low so the move counts as singular.
+            if excluded_move.is_some() {
+                return (alpha, None);
             }
-            (best_val, best_move)
+            // checkmate or stalemate
+            return (self.evaluation(board, &[], ply), None);
+        }
+
+        let bound = if best_val <= original_alpha {
+            Bound::Upper
+        } else if best_val >= beta {
+            Bound::Lower
         } else {
-            let mut best_val = 1000000;
-
-            for board_move in sorted_moves.iter() {
-                let board = board.make_move_new(*board_move);
-
-                let node_info = if self.tt_table.contains(&board) {
-                    let node_info = self.tt_table.get(&board).unwrap();
-                    // im assuming the deeper it is, the lower the depth is
-                    if node_info.depth < depth {
-                        let (eval, _) = self.internal_search(
-                            &board,
-                            max_depth,
-                            depth - 1,
-                            alpha,
-                            beta,
-                            !is_maximizing_player,
-                            Some(*board_move),
-                        );
-                        NodeInfo { eval, best_move, depth }
-                    } else {
-                        node_info.clone() // here's to hoping this function won't get called.
-                    }
-                } else {
-                    let (eval, _) = self.internal_search(
-                        &board,
-                        max_depth,
-                        depth - 1,
-                        alpha,
-                        beta,
-                        !is_maximizing_player,
-                        Some(*board_move),
-                    );
-                    NodeInfo { eval, best_move, depth }
-                };
+            Bound::Exact
+        };
+        // a fail low doesn't know which move is best.
+        let tt_move = if bound == Bound::Upper { None } else { best_move };
+        let tt_score = score::score_to_tt(best_val, ply);
+        // a search without some of the moves says nothing about the position with all of them.
+        if excluded_move.is_none()
+            && (ply > 0 || (self.root_excluded_moves.is_empty() && self.limits.search_moves.is_empty()))
+        {
+            self.tt_table.store(key, depth, tt_score, bound, tt_move);
+        }
 
-                if node_info.eval < best_val {
-                    best_val = node_info.eval;
-                    best_move = Some(*board_move);
-
-                    self.tt_table.insert(
-                        &board,
-                        NodeInfo {
-                            eval: node_info.eval,
-                            best_move,
-                            depth
-                        },
-                    );
-                    self.uci.update_depth_data(depth, max_depth, best_move);
-                }
-                beta = cmp::min(beta, best_val);
+        (best_val, best_move)
+    }
 
-                if beta <= alpha {
-                    self.update_killer_move(depth, *board_move);
-                    break;
-                }
+    /**
+     * Cuts frontier nodes whose static evaluation is far outside the window, without searching a move.
+     *
+     * - reverse futility pruning: so far above beta that no move of the opponent
+     *   is expected to bring it back, the node fails high.
+     * - razoring: so far below alpha that only a capture could help, which the quiescence
+     *   search checks instead of the full search.
+     *
+     * Only for non-pv nodes out of check and out of forcing lines (the caller makes sure),
+     * never near mate scores.
+     */
+    fn shallow_pruning(
+        &mut self,
+        board: &Board,
+        depth: u16,
+        alpha: i32,
+        beta: i32,
+        static_eval: i32,
+    ) -> Option<i32> {
+        if self.params.reverse_futility_pruning
+            && depth <= self.params.rfp_max_depth
+            && !score::is_mate_score(beta)
+            && static_eval - self.params.rfp_margin * depth as i32 >= beta
+        {
+            return Some(static_eval);
+        }
+
+        if self.params.razoring
+            && depth <= self.params.razoring_max_depth
+            && !score::is_mate_score(alpha)
+            && static_eval + self.params.razoring_margin(depth) < alpha
+        {
+            let score = self.quiescence_search(board, self.ply(), alpha, alpha + 1);
+            if self.search_aborted || score <= alpha {
+                return Some(score);
+            }
+        }
+        None
+    }
+
+    /**
+     * Lets the other side move twice in a row. If the search after that (reduced in depth)
+     * still fails high, a real move would too, so the node is cut with `beta`.
+     *
+     * Not done in check (passing would be illegal), after another null move, near mate scores,
+     * in forcing lines (the caller makes sure) where a threat needs an answer,
+     * or without pieces other than pawns, where zugzwang makes passing the best "move".
+     * Deep cutoffs are verified by a reduced normal search, to catch the zugzwangs left.
+     */
+    fn null_move_pruning(
+        &mut self,
+        board: &Board,
+        max_depth: u16,
+        depth: u16,
+        beta: i32,
+        static_eval: Option<i32>,
+        previous_move: Option<ChessMove>,
+    ) -> Option<i32> {
+        let ply = self.ply();
+        let pieces = board.pieces(Piece::Knight)
+            | board.pieces(Piece::Bishop)
+            | board.pieces(Piece::Rook)
+            | board.pieces(Piece::Queen);
+
+        if !self.params.null_move_pruning
+            || ply == 0
+            || ply < self.null_move_min_ply
+            || previous_move.is_none()
+            || depth < self.params.null_move_min_depth
+            || *board.checkers() != EMPTY
+            || pieces & board.color_combined(board.side_to_move()) == EMPTY
+            || score::is_mate_score(beta)
+            || static_eval.is_none_or(|eval| eval < beta)
+        {
+            return None;
+        }
+
+        let new_board = board.null_move()?;
+        let reduction =
+            self.params.null_move_reduction + depth / self.params.null_move_depth_divisor.max(1);
+        let null_depth = depth.saturating_sub(1 + reduction);
+
+        self.line_extensions[ply + 1] = self.line_extensions[ply];
+        self.sacrifices[ply + 1] = false;
+        self.push_null_position(board, &new_board);
+        let (score, _) =
+            self.internal_search(&new_board, max_depth, null_depth, -beta, -beta + 1, None);
+        let score = -score;
+        self.pop_position();
+
+        if self.search_aborted || score < beta {
+            return None;
+        }
+        // a mate found after passing isn't a real mate.
+        let score = if score::is_mate_score(score) { beta } else { score };
+
+        if depth < self.params.null_move_verification_depth {
+            return Some(score);
+        }
+
+        // searched without null moves for the next few plies, the same way the null move was.
+        let null_move_min_ply = self.null_move_min_ply;
+        self.null_move_min_ply = ply + 1 + 3 * null_depth as usize / 4;
+        let (verified, _) =
+            self.internal_search(board, max_depth, null_depth, beta - 1, beta, previous_move);
+        self.null_move_min_ply = null_move_min_ply;
+
+        (!self.search_aborted && verified >= beta).then_some(score)
+    }
+
+    /**
+     * Whether the hash move is much better than every other move: the other moves are searched
+     * (with less depth) against a window below the hash move's score, if none of them reach it,
+     * the hash move is the only good move and worth a deeper look.
+     */
+    fn is_singular(
+        &mut self,
+        board: &Board,
+        max_depth: u16,
+        depth: u16,
+        chess_move: ChessMove,
+        tt_entry: Option<TTEntry>,
+        previous_move: Option<ChessMove>,
+    ) -> bool {
+        let ply = self.ply();
+        let Some(entry) = tt_entry.filter(|entry| entry.best_move == Some(chess_move)) else {
+            return false;
+        };
+        let tt_score = score::score_from_tt(entry.score, ply);
+        if !self.params.singular_extensions
+            || ply == 0
+            || self.excluded_moves[ply].is_some()
+            || depth < self.params.singular_min_depth
+            || entry.depth + 3 < depth
+            || entry.bound == Bound::Upper
+            || score::is_mate_score(tt_score)
+        {
+            return false;
+        }
+
+        let singular_beta = tt_score - self.params.singular_margin * depth as i32;
+        self.excluded_moves[ply] = Some(chess_move);
+        let (score, _) = self.internal_search(
+            board,
+            max_depth,
+            (depth - 1) / 2,
+            singular_beta - 1,
+            singular_beta,
+            previous_move,
+        );
+        self.excluded_moves[ply] = None;
+
+        !self.search_aborted && score < singular_beta
+    }
+
+    /**
+     * Keeps searching captures and promotions past the horizon until the position is quiet,
+     * so the search doesn't stop in the middle of an exchange.
+     * In check every evasion is searched instead, a capture that checks can mate.
+     *
+     * Negamax, returns the score relative to the side to move.
+     */
+    pub fn quiescence_search(&mut self, board: &Board, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        self.count_node();
+        if self.should_abort() {
+            return 0;
+        }
+        if ply >= MAX_PLY - 1 {
+            return self.static_evaluation(board);
+        }
+
+        let (mut best_val, mut move_picker) = if *board.checkers() != EMPTY {
+            // no standing pat in check, and no evasion means checkmate.
+            (score::mated_in(ply), MovePicker::new(board, None, &[], None, None))
+        } else {
+            // "stand pat": the side to move isn't forced to capture,
+            // so the static evaluation is a lower bound of the score.
+            let stand_pat = self.static_evaluation(board);
+            if stand_pat >= beta {
+                return stand_pat;
+            }
+            // MVV-LVA, the most valuable victim taken by the least valuable aggressor goes first.
+            // captures that lose material (by SEE) won't raise alpha, as far as we can tell.
+            (stand_pat, MovePicker::captures(board))
+        };
+        alpha = cmp::max(alpha, best_val);
+
+        while let Some(chess_move) = move_picker.next_move(board, &self.history) {
+            let board = board.make_move_new(chess_move);
+            let eval = -self.quiescence_search(&board, ply + 1, -beta, -alpha);
+            if self.search_aborted {
+                return 0;
+            }
+
+            if eval > best_val {
+                best_val = eval;
             }
+            alpha = cmp::max(alpha, best_val);
 
-            (best_val, best_move)
+            if beta <= alpha {
+                break;
+            }
         }
+
+        best_val
     }
 
     fn update_killer_move(&mut self, depth: u16, board_move: ChessMove) {
@@ -230,3 +992,545 @@ impl BasicBot {
         self.killer_moves[depth as usize][0] = Some(board_move);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{BasicBot, MAX_DEPTH};
+    use crate::bots::bot_traits::{BoardEvaluator, Evaluation, Search};
+    use crate::bots::search_params::SearchParams;
+    use crate::bots::time_manager::TimeManager;
+    use crate::types::score;
+    use chess::{Board, ChessMove, MoveGen, Square};
+    use std::str::FromStr;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    const POSITIONS: [&str; 5] = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "6k1/5ppp/8/8/8/8/q4PPP/3R2K1 b - - 0 1",
+    ];
+
+    /**
+     * The same position with the colors swapped and the board flipped vertically.
+     */
+    fn mirror_fen(fen: &str) -> String {
+        let parts: Vec<&str> = fen.split(' ').collect();
+        let swap_case = |c: char| {
+            if c.is_ascii_uppercase() {
+                c.to_ascii_lowercase()
+            } else {
+                c.to_ascii_uppercase()
+            }
+        };
+
+        let ranks: Vec<String> = parts[0]
+            .split('/')
+            .rev()
+            .map(|rank| rank.chars().map(swap_case).collect())
+            .collect();
+        let side = if parts[1] == "w" { "b" } else { "w" };
+        let mut castling: Vec<char> = parts[2].chars().map(swap_case).collect();
+        castling.sort_by_key(|c| (c.is_ascii_lowercase(), "KQkq".find(*c)));
+        let castling: String = castling.into_iter().collect();
+        let en_passant = match parts[3] {
+            "-" => "-".to_string(),
+            square => {
+                let (file, rank) = square.split_at(1);
+                format!("{}{}", file, 9 - rank.parse::<u8>().unwrap())
+            }
+        };
+
+        format!(
+            "{} {} {} {} {} {}",
+            ranks.join("/"),
+            side,
+            castling,
+            en_passant,
+            parts[4],
+            parts[5]
+        )
+    }
+
+    fn mirror_square(square: Square) -> Square {
+        unsafe { Square::new(square.to_int() ^ 56) }
+    }
+
+    fn mirror_move(chess_move: ChessMove) -> ChessMove {
+        ChessMove::new(
+            mirror_square(chess_move.get_source()),
+            mirror_square(chess_move.get_dest()),
+            chess_move.get_promotion(),
+        )
+    }
+
+    /**
+     * A new bot's search of `fen` with `params`: the score, the best move and the bot,
+     * for its node count and statistics.
+     */
+    fn search_with(fen: &str, depth: u16, params: SearchParams) -> (i32, ChessMove, BasicBot) {
+        let mut bot = BasicBot::new(&Board::from_str(fen).unwrap(), 1024 * 1024);
+        bot.params = params;
+        let (eval, best_move) = bot.search(depth, TimeManager::unlimited()).unwrap();
+        (eval, best_move, bot)
+    }
+
+    #[test]
+    fn evaluation_is_symmetric() {
+        for fen in POSITIONS {
+            let board = Board::from_str(fen).unwrap();
+            let mirrored = Board::from_str(&mirror_fen(fen)).unwrap();
+            let bot = BasicBot::new(&board, 1024);
+
+            assert_eq!(
+                bot.static_evaluation(&board),
+                bot.static_evaluation(&mirrored),
+                "{}",
+                fen
+            );
+        }
+    }
+
+    #[test]
+    fn search_is_symmetric() {
+        // the move generator doesn't order mirrored moves the same way,
+        // so pruning that depends on the move order is left out.
+        let params = SearchParams {
+            late_move_reductions: false,
+            late_move_pruning: false,
+            ..SearchParams::default()
+        };
+        for fen in POSITIONS {
+            let (eval, _, _) = search_with(fen, 3, params.clone());
+            let (mirrored_eval, _, _) = search_with(&mirror_fen(fen), 3, params.clone());
+            assert_eq!(eval, mirrored_eval, "{}", fen);
+        }
+    }
+
+    fn play(bot: &mut BasicBot, moves: &[&str]) {
+        let moves: Vec<ChessMove> = moves
+            .iter()
+            .map(|chess_move| ChessMove::from_str(chess_move).unwrap())
+            .collect();
+        bot.set_position(&Board::default(), 0, &moves);
+    }
+
+    #[test]
+    fn detects_repetitions_from_the_game_history() {
+        let mut bot = BasicBot::new(&Board::default(), 1024);
+
+        play(&mut bot, &["g1f3", "g8f6", "f3g1", "f6g8"]);
+        assert!(!bot.is_repetition(), "the start position happened only twice");
+
+        play(&mut bot, &["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8"]);
+        assert!(bot.is_repetition(), "threefold repetition");
+
+        play(&mut bot, &["g1f3", "g8f6", "f3g1", "f6g8", "e2e4", "e7e5"]);
+        assert!(!bot.is_repetition(), "pawn moves can't be undone");
+    }
+
+    #[test]
+    fn recognises_insufficient_material() {
+        let bot = BasicBot::new(&Board::default(), 1024);
+        let insufficient = |fen: &str| bot.is_insufficient_material(&Board::from_str(fen).unwrap());
+
+        assert!(insufficient("4k3/8/8/8/8/8/8/4K3 w - - 0 1"));
+        assert!(insufficient("4k3/8/8/8/8/8/8/4KN2 w - - 0 1"));
+        assert!(insufficient("4k3/8/8/8/8/8/8/4KB2 w - - 0 1"));
+        assert!(insufficient("4kb2/8/8/8/8/8/8/2B1K3 w - - 0 1"));
+        assert!(!insufficient("4k1b1/8/8/8/8/8/8/2B1K3 w - - 0 1"));
+        assert!(!insufficient("4k3/8/8/8/8/8/8/3NKN2 w - - 0 1"));
+        assert!(!insufficient("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"));
+    }
+
+    #[test]
+    fn scores_a_stalemate_as_a_draw() {
+        // black isn't in check but has no legal moves.
+        let board = Board::from_str("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        let mut bot = BasicBot::new(&board, 1024);
+        assert_eq!(bot.evaluation(&board, &[], 0), 0);
+
+        // with contempt, the side we search for avoids the draw and the other side goes for it.
+        bot.contempt = 20;
+        assert_eq!(bot.evaluation(&board, &[], 0), -20);
+        assert_eq!(bot.evaluation(&board, &[], 1), 20);
+    }
+
+    #[test]
+    fn quiescence_search_sees_mates() {
+        // scholar's mate, Qxf7# is a capture.
+        let board =
+            Board::from_str("r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4").unwrap();
+        let mut bot = BasicBot::new(&board, 1024);
+        let eval = bot.quiescence_search(&board, 0, -score::INFINITY, score::INFINITY);
+        assert_eq!(eval, score::mate_in(1));
+
+        let mated = board.make_move_new(ChessMove::new(Square::H5, Square::F7, None));
+        let eval = bot.quiescence_search(&mated, 3, -score::INFINITY, score::INFINITY);
+        assert_eq!(eval, score::mated_in(3));
+    }
+
+    #[test]
+    fn finds_no_move_without_legal_moves() {
+        for fen in ["7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", "7k/6Q1/6K1/8/8/8/8/8 b - - 0 1"] {
+            let mut bot = BasicBot::new(&Board::from_str(fen).unwrap(), 1024);
+            assert_eq!(bot.search(3, TimeManager::unlimited()), None);
+        }
+    }
+
+    #[test]
+    fn plays_the_same_tactic_as_both_colors() {
+        // the rook on d1 mates on d8, black's queen can't stop it.
+        let fen = "6k1/5ppp/8/8/8/8/q4PPP/3R2K1 w - - 0 1";
+        let board = Board::from_str(fen).unwrap();
+        let mirrored = Board::from_str(&mirror_fen(fen)).unwrap();
+
+        let mut bot = BasicBot::new(&board, 1024 * 1024);
+        let (_, best_move) = bot.search(3, TimeManager::unlimited()).unwrap();
+        assert_eq!(best_move, ChessMove::new(Square::D1, Square::D8, None));
+
+        let mut mirrored_bot = BasicBot::new(&mirrored, 1024 * 1024);
+        let (_, mirrored_best_move) = mirrored_bot.search(3, TimeManager::unlimited()).unwrap();
+        assert_eq!(mirrored_best_move, mirror_move(best_move));
+    }
+
+    #[test]
+    fn check_extensions_see_mates_past_the_horizon() {
+        // smothered mate: Qg8+ Rxg8 Nf7#, three plies deep.
+        let fen = "r6k/6pp/7N/8/8/1Q6/8/6K1 w - - 0 1";
+        let (eval, best_move, _) = search_with(fen, 1, SearchParams::default());
+        assert_eq!(eval, score::mate_in(3));
+        assert_eq!(best_move, ChessMove::new(Square::B3, Square::G8, None));
+
+        let params = SearchParams {
+            check_extensions: false,
+            ..SearchParams::default()
+        };
+        let (eval, _, _) = search_with(fen, 1, params);
+        assert!(!score::is_mate_score(eval));
+
+        // Rxg8 is the only reply to the check, without its extension the mate is past the horizon.
+        for (max_line_extensions, mate) in [(1, false), (2, true)] {
+            let params = SearchParams {
+                max_line_extensions,
+                ..SearchParams::default()
+            };
+            let (eval, _, _) = search_with(fen, 1, params);
+            assert_eq!(eval == score::mate_in(3), mate, "{}", max_line_extensions);
+        }
+    }
+
+    #[test]
+    fn singular_extensions_extend_the_only_good_move() {
+        let singular = |parent: &Board, previous_move: &str, depth: u16| {
+            let previous_move = ChessMove::from_str(previous_move).unwrap();
+            let board = parent.make_move_new(previous_move);
+            let mut bot = BasicBot::new(&board, 1024 * 1024);
+            bot.search(depth, TimeManager::unlimited());
+            let hash_move = bot.uci.lines[0].best_move.unwrap();
+
+            // the root is never extended, the position is searched again one ply deeper.
+            bot.set_position(parent, 0, &[]);
+            bot.push_position(parent, previous_move, &board);
+            let tt_entry = bot.tt_table.probe(*bot.position_keys.last().unwrap());
+            let depth = bot.params.singular_min_depth;
+            bot.is_singular(&board, MAX_DEPTH, depth, hash_move, tt_entry, Some(previous_move))
+        };
+
+        // only Rxd5 wins the queen.
+        let parent = Board::from_str("4k3/8/8/8/3q4/8/8/3RK3 b - - 0 1").unwrap();
+        assert!(singular(&parent, "d4d5", 6));
+        // black has many good moves after 1. e4 e5 2. Nf3.
+        let parent = Board::from_str("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2").unwrap();
+        assert!(!singular(&parent, "g1f3", 6));
+
+        // extending from a shallower depth (so it happens a lot) still finds the right move.
+        let params = SearchParams {
+            singular_min_depth: 4,
+            ..SearchParams::default()
+        };
+        let (_, extended, extended_bot) = search_with(POSITIONS[4], 7, params.clone());
+        let params = SearchParams {
+            singular_extensions: false,
+            ..params
+        };
+        let (_, best_move, bot) = search_with(POSITIONS[4], 7, params);
+        assert_eq!(extended, best_move);
+        assert!(extended_bot.uci.nodes_total > bot.uci.nodes_total);
+    }
+
+    #[test]
+    fn pruning_keeps_shallow_mates() {
+        // a queen down after Qg8+ Rxg8, and a rook lift before the back rank mate: far below alpha
+        // for razoring and futility pruning, but the checks and the mate scores guard them.
+        // WAC.001: Qg6 hangs the queen (and is reduced, a late quiet move), the static evaluation
+        // of the replies doesn't see the mate threats, Qh7# and Nxg6#.
+        let mates = [
+            ("r6k/6pp/7N/8/8/1Q6/8/6K1 w - - 0 1", 3, 3, ChessMove::new(Square::B3, Square::G8, None)),
+            ("7k/8/8/8/8/8/R7/1R4K1 w - - 0 1", 3, 3, ChessMove::new(Square::B1, Square::B7, None)),
+            (
+                "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1",
+                3,
+                4,
+                ChessMove::new(Square::G3, Square::G6, None),
+            ),
+        ];
+        for (fen, ply, min_depth, mating_move) in mates {
+            for depth in min_depth..=5 {
+                // every pruning is on by default.
+                let (eval, best_move, _) = search_with(fen, depth, SearchParams::default());
+                assert_eq!(eval, score::mate_in(ply), "{} depth {}", fen, depth);
+                assert_eq!(best_move, mating_move, "{} depth {}", fen, depth);
+            }
+        }
+    }
+
+    #[test]
+    fn multi_pv_finds_different_moves_best_first() {
+        let board = Board::from_str(POSITIONS[1]).unwrap();
+        let mut bot = BasicBot::new(&board, 1024 * 1024);
+        let lines = bot.analyse(4, 3, TimeManager::unlimited());
+
+        assert_eq!(lines.len(), 3);
+        for (index, line) in lines.iter().enumerate() {
+            assert_eq!(line.multi_pv, index + 1);
+            assert_eq!(line.best_move, line.pv.first().copied());
+        }
+        assert!(lines.windows(2).all(|pair| pair[0].eval >= pair[1].eval));
+        assert!(lines[0].best_move != lines[1].best_move && lines[1].best_move != lines[2].best_move);
+        assert!(lines[0].best_move != lines[2].best_move);
+        assert_eq!(bot.multi_pv, 1);
+
+        // the king has a single legal move (a7) can't have more lines.
+        let mut bot = BasicBot::new(&Board::from_str("k7/8/2K5/8/8/8/8/1R6 b - - 0 1").unwrap(), 1024);
+        assert_eq!(bot.analyse(2, 3, TimeManager::unlimited()).len(), 1);
+    }
+
+    #[test]
+    fn analyses_the_same_bot_again_with_a_time_limit() {
+        let mut bot = BasicBot::new(&Board::default(), 1024 * 1024);
+        // the first iteration always finishes, with every line, however short the time,
+        // and nothing is left over from the last position.
+        for fen in [POSITIONS[1], POSITIONS[2], POSITIONS[1]] {
+            let board = Board::from_str(fen).unwrap();
+            bot.set_position(&board, 0, &[]);
+            let lines = bot.analyse(MAX_DEPTH, 2, TimeManager::move_time(Duration::from_millis(1)));
+
+            assert_eq!(lines.len(), 2, "{}", fen);
+            assert!(lines.iter().all(|line| board.legal(line.best_move.unwrap())), "{}", fen);
+            assert!(lines[0].best_move != lines[1].best_move);
+            assert!(lines[0].eval >= lines[1].eval);
+            assert!(!bot.uci.depth_data.is_empty());
+        }
+    }
+
+    #[test]
+    fn pv_reaches_the_search_depth() {
+        for fen in &POSITIONS[..3] {
+            let board = Board::from_str(fen).unwrap();
+            let mut bot = BasicBot::new(&board, 1024 * 1024);
+            bot.search(6, TimeManager::unlimited());
+            bot.search(8, TimeManager::unlimited());
+
+            // the second search finds the positions of its pv in the table, from the first,
+            // which mustn't cut the pv short.
+            for data in &bot.uci.depth_data {
+                assert!(data.pv.len() >= data.depth as usize, "{} {:?}", fen, data.pv);
+                let mut position = board;
+                for chess_move in &data.pv {
+                    assert!(position.legal(*chess_move), "{} {:?}", fen, data.pv);
+                    position = position.make_move_new(*chess_move);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn searches_only_the_searchmoves() {
+        let a3 = ChessMove::new(Square::A2, Square::A3, None);
+        let h3 = ChessMove::new(Square::H2, Square::H3, None);
+        let mut bot = BasicBot::new(&Board::default(), 1024 * 1024);
+        // an illegal searchmove is dropped.
+        bot.limits.search_moves = vec![a3, h3, ChessMove::new(Square::E2, Square::E5, None)];
+        let lines = bot.analyse(3, 5, TimeManager::unlimited());
+
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|line| line.best_move == Some(a3) || line.best_move == Some(h3)));
+    }
+
+    #[test]
+    fn stops_at_the_node_limit() {
+        let mut bot = BasicBot::new(&Board::from_str(POSITIONS[2]).unwrap(), 1024 * 1024);
+        bot.limits.nodes = Some(20000);
+        bot.search(MAX_DEPTH, TimeManager::unlimited());
+        assert!(bot.uci.nodes_total <= 20001, "{}", bot.uci.nodes_total);
+
+        // the next search starts counting again, and still finishes its first iteration.
+        bot.limits.nodes = Some(10);
+        assert!(bot.search(MAX_DEPTH, TimeManager::unlimited()).is_some());
+        assert_eq!(bot.uci.depth_data.len(), 1);
+    }
+
+    #[test]
+    fn stops_once_the_mate_is_found() {
+        let board = Board::from_str("r6k/6pp/7N/8/8/1Q6/8/6K1 w - - 0 1").unwrap();
+        let mut bot = BasicBot::new(&board, 1024 * 1024);
+        bot.limits.mate = Some(2);
+        let (eval, _) = bot.search(MAX_DEPTH, TimeManager::unlimited()).unwrap();
+
+        assert_eq!(eval, score::mate_in(3));
+        assert!(bot.uci.depth_data.len() < 5);
+    }
+
+    #[test]
+    fn helper_threads_find_the_same_move() {
+        let board = Board::from_str("6k1/5ppp/8/8/8/8/q4PPP/3R2K1 w - - 0 1").unwrap();
+        let mut bot = BasicBot::new(&board, 1024 * 1024);
+        bot.threads = 4;
+        // the helpers are kept for the next search.
+        for _ in 0..2 {
+            let (eval, best_move) = bot.search(5, TimeManager::unlimited()).unwrap();
+            assert_eq!(best_move, ChessMove::new(Square::D1, Square::D8, None));
+            assert_eq!(eval, score::mate_in(1));
+            assert!(bot.nodes() >= bot.uci.nodes_total);
+            bot.reset();
+        }
+        assert_eq!(bot.helpers.len(), 3);
+    }
+
+    #[test]
+    fn helpers_add_the_same_noise() {
+        let board = Board::from_str(POSITIONS[1]).unwrap();
+        let mut bot = BasicBot::new(&board, 1024 * 1024);
+        bot.threads = 3;
+        // seed 0 draws a new random seed every search, the helpers have to use the same one.
+        bot.skill.level = 0;
+        bot.search(3, TimeManager::unlimited()).unwrap();
+        let noise = |bot: &BasicBot| {
+            let mut position = board;
+            MoveGen::new_legal(&board)
+                .map(|chess_move| {
+                    board.make_move(chess_move, &mut position);
+                    bot.skill.noise(&position)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert!(noise(&bot).iter().any(|noise| *noise != 0));
+        for helper in &bot.helpers {
+            assert_eq!(noise(helper), noise(&bot));
+        }
+    }
+
+    #[test]
+    fn pondering_holds_the_move_until_ponderhit() {
+        let mut bot = BasicBot::new(&Board::default(), 1024 * 1024);
+        let pondering = Arc::new(AtomicBool::new(true));
+        let ponderhit = {
+            let pondering = Arc::clone(&pondering);
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(100));
+                pondering.store(false, Ordering::Relaxed);
+            })
+        };
+
+        // depth 3 and the movetime are both done long before the ponderhit.
+        let start = Instant::now();
+        let time_manager = TimeManager::move_time(Duration::from_millis(30)).ponder(pondering);
+        bot.search(3, time_manager);
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        ponderhit.join().unwrap();
+    }
+
+    #[test]
+    fn null_move_pruning_skips_pawn_endings() {
+        // zugzwang: whoever has to move here loses the d-pawn.
+        let fen = "8/8/4k3/3p4/3P4/4K3/8/8 w - - 0 1";
+        let (_, _, bot) = search_with(fen, 6, SearchParams::default());
+        let params = SearchParams {
+            null_move_pruning: false,
+            ..SearchParams::default()
+        };
+        let (_, _, full_bot) = search_with(fen, 6, params);
+        assert_eq!(bot.uci.nodes_total, full_bot.uci.nodes_total);
+    }
+
+    #[test]
+    fn late_move_reductions_keep_the_best_move() {
+        let params = SearchParams {
+            late_move_pruning: false,
+            ..SearchParams::default()
+        };
+        for fen in [POSITIONS[0], POSITIONS[1], POSITIONS[3]] {
+            let (_, best_move, bot) = search_with(fen, 6, params.clone());
+            let full_params = SearchParams {
+                late_move_reductions: false,
+                ..params.clone()
+            };
+            let (_, full_best_move, full_bot) = search_with(fen, 6, full_params);
+
+            // the reduced moves that look better are searched again, to the full depth,
+            // so the best move doesn't change.
+            assert!(bot.reduced_researches > 0, "{}", fen);
+            assert_eq!(full_bot.reduced_researches, 0, "{}", fen);
+            assert_eq!(best_move, full_best_move, "{}", fen);
+            let (nodes, full_nodes) = (bot.uci.nodes_total, full_bot.uci.nodes_total);
+            assert!(nodes < full_nodes, "{}: {} nodes, {} without reductions", fen, nodes, full_nodes);
+        }
+    }
+
+    #[test]
+    fn aspiration_windows_find_the_full_window_score() {
+        // without the pruning that depends on the window, a search that falls outside its window
+        // and is searched again has to end up with the full window's score.
+        let params = SearchParams {
+            null_move_pruning: false,
+            late_move_reductions: false,
+            late_move_pruning: false,
+            reverse_futility_pruning: false,
+            futility_pruning: false,
+            razoring: false,
+            ..SearchParams::default()
+        };
+        for (fen, depth) in [(POSITIONS[1], 5), (POSITIONS[2], 4)] {
+            let search = |aspiration_min_depth: u16, aspiration_delta: i32| {
+                let params = SearchParams {
+                    aspiration_min_depth,
+                    aspiration_delta,
+                    ..params.clone()
+                };
+                let (eval, best_move, _) = search_with(fen, depth, params);
+                (eval, best_move)
+            };
+            let full_window = search(MAX_DEPTH + 1, params.aspiration_delta);
+            // a window of 1 centipawn is missed almost every time.
+            assert_eq!(search(params.aspiration_min_depth, 1), full_window, "{}", fen);
+            assert_eq!(search(params.aspiration_min_depth, params.aspiration_delta), full_window, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn new_game_forgets_the_last_game() {
+        let board = Board::from_str(POSITIONS[1]).unwrap();
+        let search = |bot: &mut BasicBot| {
+            bot.set_position(&board, 0, &[]);
+            let (eval, best_move) = bot.search(5, TimeManager::unlimited()).unwrap();
+            let nodes = bot.uci.nodes_total;
+            bot.reset();
+            (eval, best_move, nodes)
+        };
+        let mut fresh_bot = BasicBot::new(&board, 1024 * 1024);
+        let expected = search(&mut fresh_bot);
+
+        // the table, killers and history of another game would change the move ordering, and the node count.
+        let mut bot = BasicBot::new(&Board::from_str(POSITIONS[2]).unwrap(), 1024 * 1024);
+        bot.search(5, TimeManager::unlimited());
+        bot.reset();
+        bot.new_game();
+        assert_eq!(bot.board, Board::default());
+        assert_eq!(search(&mut bot), expected);
+    }
+}
@@ -1,97 +1,321 @@
-use crate::{bots::basic_bot::BasicBot, types::pieces_colored::PiecesColored};
-use chess::{Board, ChessMove, Color, Piece, ALL_SQUARES};
-
-use std::time::Instant;
+use crate::bots::skill::{Skill, SKILL_MULTI_PV};
+use crate::bots::time_manager::TimeManager;
+use crate::tables::transposition::Bound;
+use crate::types::score;
+use crate::uci::uci::DepthData;
+use crate::{bots::basic_bot::{BasicBot, MAX_DEPTH}, types::pieces_colored::PiecesColored};
+use chess::{BitBoard, Board, ChessMove, Color, MoveGen, Piece, ALL_SQUARES, EMPTY};
+
+use std::cmp;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
 
 pub trait Search {
-    fn search(&mut self, depth: u16) -> (i32, ChessMove);
+    /**
+     * The score and the best move of the position, None if it has no legal move.
+     */
+    fn search(&mut self, depth: u16, time_manager: TimeManager) -> Option<(i32, ChessMove)>;
+
+    /**
+     * Searches the `lines` best moves of the root instead of only the best one,
+     * returns their lines (score and pv) best first. For hints and game reviews.
+     */
+    fn analyse(&mut self, depth: u16, lines: usize, time_manager: TimeManager) -> Vec<DepthData>;
 }
 
 impl Search for BasicBot {
     // external function, interacts with self
-    fn search(&mut self, depth: u16) -> (i32, ChessMove) {
-        let board = self.board.clone();
-        let alpha = -999999; // Negative infinity
-        let beta = 999999; // Positive infinity
-        let start = Instant::now();
-
-        let (best_eval, best_move) =
-            self.internal_search(&board, depth, depth, alpha, beta, true, None);
-        self.uci.set_ms_passed(start.elapsed().as_millis() as u64);
-
-        let best_move = match best_move {
-            Some(best_move) => best_move,
-            None => panic!("Something went wrong with searching the best move."),
+    fn search(&mut self, depth: u16, time_manager: TimeManager) -> Option<(i32, ChessMove)> {
+        let board = self.board;
+        self.time_manager = time_manager;
+        self.clear_search();
+        // the helpers share the table, it only ages once per search.
+        if self.thread_index == 0 {
+            self.tt_table.new_search();
+        }
+        self.reduction_table = self.params.reduction_table();
+        self.history.decay();
+        self.truncate_to_root();
+
+        // searchmoves that can't be played are ignored, and so are searchmoves without a legal one.
+        self.limits.search_moves.retain(|chess_move| board.legal(*chess_move));
+        // there can't be more lines than moves to search.
+        let root_moves = MoveGen::new_legal(&board)
+            .filter(|chess_move| self.limits.is_root_move(*chess_move))
+            .count();
+        let mut multi_pv = self.multi_pv;
+
+        // a weakened bot doesn't look as far, and picks among its best few moves.
+        if self.thread_index == 0 {
+            self.skill.new_search(&board);
+        }
+        let skill_level = self.skill.effective_level();
+        let mut depth = depth;
+        let node_limit = self.limits.nodes;
+        if let Some(level) = skill_level {
+            depth = cmp::min(depth, Skill::depth_limit(level));
+            self.limits.nodes = Some(cmp::min(
+                node_limit.unwrap_or(u64::MAX),
+                Skill::node_limit(level),
+            ));
+            multi_pv = cmp::max(multi_pv, SKILL_MULTI_PV);
+        }
+        let multi_pv = multi_pv.clamp(1, cmp::max(root_moves, 1));
+
+        let mut best = self.with_helpers(|bot| bot.iterative_deepening(&board, depth, multi_pv));
+        self.limits.nodes = node_limit;
+        if let Some(level) = skill_level {
+            if let Some(line) = self.skill.pick(&self.uci.lines, level) {
+                best = line.best_move.map(|best_move| (line.eval, best_move)).or(best);
+            }
+        }
+
+        // "go infinite" must not answer before "stop", and "go ponder" not before "stop" or "ponderhit",
+        // even if we ran out of depth.
+        while (self.time_manager.is_infinite() || self.time_manager.is_pondering())
+            && !self.stop.load(Ordering::Relaxed)
+        {
+            thread::sleep(Duration::from_millis(1));
+            self.time_manager.check_ponderhit();
+        }
+
+        // the first iteration is never aborted, it only finds no move if there is none.
+        best
+    }
+
+    fn analyse(&mut self, depth: u16, lines: usize, time_manager: TimeManager) -> Vec<DepthData> {
+        let multi_pv = self.multi_pv;
+        self.multi_pv = lines;
+        self.search(depth, time_manager);
+        self.multi_pv = multi_pv;
+        self.uci.lines.iter().take(lines).cloned().collect()
+    }
+}
+
+impl BasicBot {
+    /**
+     * Searches the root again and again, one ply deeper every time, until `depth`
+     * or one of the limits is reached. Returns the best move of the last finished iteration.
+     */
+    fn iterative_deepening(
+        &mut self,
+        board: &Board,
+        depth: u16,
+        multi_pv: usize,
+    ) -> Option<(i32, ChessMove)> {
+        let mut best = None;
+
+        // iterative deepening: every iteration leaves its best move and the tt entries
+        // behind, which the next (deeper) iteration uses to order its moves.
+        for current_depth in 1..=depth {
+            // helpers with an odd index search one ply deeper, so the threads don't all
+            // search the same tree at the same time.
+            let search_depth = cmp::min(current_depth + (self.thread_index % 2) as u16, MAX_DEPTH);
+            // multi-pv: every line is a search of the root without the best moves of the lines before it.
+            self.root_excluded_moves.clear();
+            let mut lines = Vec::with_capacity(multi_pv);
+            for line in 1..=multi_pv {
+                let Some(data) = self.search_line(board, search_depth, line) else {
+                    break;
+                };
+                if let Some(best_move) = data.best_move {
+                    self.root_excluded_moves.push(best_move);
+                }
+                lines.push(data);
+            }
+            self.root_excluded_moves.clear();
+            // an aborted iteration didn't look at every move, so its result can't be trusted.
+            if lines.len() < multi_pv {
+                break;
+            }
+
+            // a later line can come out a bit better than an earlier one, the search isn't exact.
+            lines.sort_by_key(|line| cmp::Reverse(line.eval));
+            for (index, line) in lines.iter_mut().enumerate() {
+                line.multi_pv = index + 1;
+            }
+            if let Some(best_move) = lines[0].best_move {
+                best = Some((lines[0].eval, best_move));
+            }
+            self.uci.report_depth(lines[0].clone());
+            // a weakened bot searches more lines than asked for, only to pick among them.
+            for line in lines.iter().take(self.multi_pv).skip(1) {
+                self.uci.print_info(line);
+            }
+            self.uci.lines = lines;
+
+            self.time_manager.check_ponderhit();
+            if self.time_manager.soft_limit_reached()
+                || self.limits.nodes_reached(self.nodes())
+                || self.limits.mate_found(self.uci.lines[0].eval)
+            {
+                break;
+            }
+        }
+        best
+    }
+
+    /**
+     * One line of an iteration, searched with an aspiration window: the score usually doesn't
+     * move far from the same line's score in the last iteration, and a narrow window around it cuts more.
+     * If the score falls outside, the window widens.
+     *
+     * None if the search was aborted.
+     */
+    fn search_line(&mut self, board: &Board, depth: u16, line: usize) -> Option<DepthData> {
+        let mut delta = self.params.aspiration_delta;
+        let (mut alpha, mut beta) = match self.uci.lines.get(line - 1) {
+            Some(last)
+                if depth >= self.params.aspiration_min_depth && !score::is_mate_score(last.eval) =>
+            {
+                (
+                    cmp::max(last.eval - delta, -score::INFINITY),
+                    cmp::min(last.eval + delta, score::INFINITY),
+                )
+            }
+            _ => (-score::INFINITY, score::INFINITY),
         };
 
-        (best_eval, best_move)
+        loop {
+            let (eval, best_move) = self.internal_search(board, depth, depth, alpha, beta, None);
+            if self.should_abort() {
+                return None;
+            }
+            let ms_passed = self.time_manager.elapsed().as_millis() as u64;
+            self.uci.set_ms_passed(ms_passed);
+
+            let data = DepthData {
+                depth,
+                multi_pv: line,
+                eval,
+                best_move,
+                pv: self.pv_table[0].clone(),
+                bound: if eval <= alpha {
+                    Bound::Upper
+                } else if eval >= beta {
+                    Bound::Lower
+                } else {
+                    Bound::Exact
+                },
+                node_count: self.nodes(),
+                ms_passed,
+                hashfull: self.tt_table.hashfull(),
+            };
+
+            match data.bound {
+                Bound::Exact => return Some(data),
+                Bound::Upper => {
+                    beta = (alpha + beta) / 2;
+                    alpha = if score::is_mate_score(eval) {
+                        -score::INFINITY
+                    } else {
+                        cmp::max(eval - delta, -score::INFINITY)
+                    };
+                }
+                Bound::Lower => {
+                    beta = if score::is_mate_score(eval) {
+                        score::INFINITY
+                    } else {
+                        cmp::min(eval + delta, score::INFINITY)
+                    };
+                }
+            }
+            self.uci.print_info(&data);
+            delta *= 2;
+        }
     }
 }
 
 pub trait Evaluation {
-    fn evaluation(&self, board: &Board, moves: &Vec<ChessMove>, is_maximizing_player: bool) -> i32;
+    fn evaluation(&self, board: &Board, moves: &[ChessMove], ply: usize) -> i32;
+    fn static_evaluation(&self, board: &Board) -> i32;
 }
 
 impl Evaluation for BasicBot {
-    fn evaluation(&self, board: &Board, moves: &Vec<ChessMove>, is_maximizing_player: bool) -> i32 {
-        // all of these functions subtract from black and white and vice versa
-        // should we pass in the "maximizing_player" boolean instead of praying White will be the
-        // maximizing player?
+    /**
+     * Relative to the side to move, like every function it calls.
+     */
+    fn evaluation(&self, board: &Board, moves: &[ChessMove], ply: usize) -> i32 {
+        match self.evaluate_mates(board, moves, ply) {
+            Some(score) => score,
+            None => self.static_evaluation(board),
+        }
+    }
+
+    /**
+     * Material and PeSTO tables only (and the noise of a weakened bot), relative to the side to move.
+     * Used as the "stand pat" score of the quiescence search.
+     */
+    fn static_evaluation(&self, board: &Board) -> i32 {
         let material = self.evaluate_material_advantage(board);
         let position = self.evaluate_piece_sq_table(board);
-        let check = self.evaluate_mates(board, moves, is_maximizing_player);
 
-        material + position as i32 + check
+        material + position as i32 + self.skill.noise(board)
     }
 }
 
 pub trait BoardEvaluator {
     fn evaluate_material_advantage(&self, board: &Board) -> i32;
     fn evaluate_piece_sq_table(&self, board: &Board) -> f32;
-    fn evaluate_mates(
-        &self,
-        board: &Board,
-        moves: &Vec<ChessMove>,
-        is_maximizing_player: bool,
-    ) -> i32;
+    fn evaluate_mates(&self, board: &Board, moves: &[ChessMove], ply: usize) -> Option<i32>;
+    fn is_insufficient_material(&self, board: &Board) -> bool;
 }
 
 impl BoardEvaluator for BasicBot {
-    fn evaluate_mates(
-        &self,
-        board: &Board,
-        moves: &Vec<ChessMove>,
-        is_maximizing_player: bool,
-    ) -> i32 {
-        let perspective = if is_maximizing_player { -1 } else { 1 };
-        let check = if moves.len() == 0 {
-            let checkers = board.checkers();
-            if checkers.popcnt() >= 1 {
-                // checkmate
-                999999 * perspective
-            } else {
-                // stalemate
-                555555 * perspective
-            }
+    /**
+     * The score of a position without legal moves, None if there are moves left.
+     * Getting mated closer to the root is worse.
+     */
+    fn evaluate_mates(&self, board: &Board, moves: &[ChessMove], ply: usize) -> Option<i32> {
+        if !moves.is_empty() {
+            return None;
+        }
+        let checkers = board.checkers();
+        if checkers.popcnt() >= 1 {
+            // checkmate
+            Some(score::mated_in(ply))
         } else {
-            0
-        };
+            // stalemate
+            Some(self.draw_score(ply))
+        }
+    }
 
-        check
+    /**
+     * Neither side can checkmate: bare kings, a single minor piece,
+     * or only bishops that all stand on squares of the same color.
+     */
+    fn is_insufficient_material(&self, board: &Board) -> bool {
+        let heavy_pieces =
+            board.pieces(Piece::Pawn) | board.pieces(Piece::Rook) | board.pieces(Piece::Queen);
+        if heavy_pieces != EMPTY {
+            return false;
+        }
+
+        let knights = board.pieces(Piece::Knight).popcnt();
+        let bishops = *board.pieces(Piece::Bishop);
+        match (knights, bishops.popcnt()) {
+            (0, 0) | (1, 0) | (0, 1) => true,
+            (0, _) => {
+                // a1, c1, e1, g1, b2, d2...
+                let dark_squares = BitBoard::new(0xAA55_AA55_AA55_AA55);
+                (bishops & dark_squares) == EMPTY || (bishops & !dark_squares) == EMPTY
+            }
+            _ => false,
+        }
     }
     fn evaluate_material_advantage(&self, board: &Board) -> i32 {
-        let white = PiecesColored::get_colored_pieces(&board, Color::White);
-        let black = PiecesColored::get_colored_pieces(&board, Color::Black);
+        let white = PiecesColored::get_colored_pieces(board, Color::White);
+        let black = PiecesColored::get_colored_pieces(board, Color::Black);
 
         let material_white = self.calculate_material(white) as i32;
         let material_black = self.calculate_material(black) as i32;
 
-        let eval = if board.side_to_move() == Color::White {
+        if board.side_to_move() == Color::White {
             material_white - material_black
         } else {
             material_black - material_white
-        };
-        return eval;
+        }
     }
 
     fn evaluate_piece_sq_table(&self, board: &Board) -> f32 {
@@ -109,40 +333,7 @@ impl BoardEvaluator for BasicBot {
             )
         };
 
-        let score = self.calculate_score(board, mg_score, eg_score);
-
-        score
-    }
-}
-
-pub trait ChessScoring {
-    fn mvv_lva_score(&self, chess_move: &ChessMove, board: &Board) -> Option<i32>;
-    fn piece_value(&self, piece: Piece) -> i32;
-}
-
-impl ChessScoring for BasicBot {
-    fn mvv_lva_score(&self, chess_move: &ChessMove, board: &Board) -> Option<i32> {
-        let victim_piece = board.piece_on(chess_move.get_dest());
-        let aggressor_piece = board.piece_on(chess_move.get_source());
-
-        match (victim_piece, aggressor_piece) {
-            (Some(victim), Some(aggressor)) => {
-                let victim_value = self.piece_value(victim);
-                let aggressor_value = self.piece_value(aggressor);
-                Some(victim_value - aggressor_value)
-            }
-            _ => None,
-        }
-    }
-
-    fn piece_value(&self, piece: Piece) -> i32 {
-        match piece {
-            Piece::Pawn => 1,
-            Piece::Knight | Piece::Bishop => 3,
-            Piece::Rook => 5,
-            Piece::Queen => 9,
-            Piece::King => std::i32::MAX,
-        }
+        self.calculate_score(board, mg_score, eg_score)
     }
 }
 
@@ -1,2 +1,6 @@
 pub mod basic_bot;
 pub mod bot_traits;
+pub mod search_limits;
+pub mod search_params;
+pub mod skill;
+pub mod time_manager;
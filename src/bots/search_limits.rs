@@ -0,0 +1,43 @@
+use chess::ChessMove;
+
+use crate::types::score;
+
+/**
+ * What a "go" limits besides the time and the depth. Whichever limit is reached first
+ * ends the search, these combine with the time manager's.
+ */
+#[derive(Clone, Debug, Default)]
+pub struct SearchLimits {
+    /**
+     * "go searchmoves", only these moves are searched at the root. Every move if empty.
+     */
+    pub search_moves: Vec<ChessMove>,
+    /**
+     * "go nodes", the search gives up after this many nodes.
+     */
+    pub nodes: Option<u64>,
+    /**
+     * "go mate", the search ends as soon as it finds a mate in this many moves or less.
+     */
+    pub mate: Option<u16>,
+}
+
+impl SearchLimits {
+    pub fn is_root_move(&self, chess_move: ChessMove) -> bool {
+        self.search_moves.is_empty() || self.search_moves.contains(&chess_move)
+    }
+
+    pub fn nodes_reached(&self, nodes: u64) -> bool {
+        self.nodes.is_some_and(|limit| nodes >= limit)
+    }
+
+    /**
+     * Whether `eval` (of the root) proves the mate "go mate" asked for.
+     */
+    pub fn mate_found(&self, eval: i32) -> bool {
+        match (self.mate, score::mate_distance(eval)) {
+            (Some(mate), Some(moves)) => moves > 0 && moves <= mate as i32,
+            _ => false,
+        }
+    }
+}
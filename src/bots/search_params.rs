@@ -0,0 +1,168 @@
+use crate::bots::basic_bot::MAX_DEPTH;
+
+/**
+ * Reductions are only looked up for this many moves, later moves get the last column.
+ */
+pub const MAX_REDUCED_MOVES: usize = 64;
+
+/**
+ * The knobs of the search's pruning and reductions, kept together so they can be tuned
+ * (and switched off for analysis) without touching the search itself.
+ */
+#[derive(Clone, Debug)]
+pub struct SearchParams {
+    /**
+     * Null-move pruning: if passing the turn still fails high, the position is good enough
+     * to be cut without searching any real move.
+     */
+    pub null_move_pruning: bool,
+    pub null_move_min_depth: u16,
+    /**
+     * Base depth reduction of the null-move search, one more ply every `null_move_depth_divisor`.
+     */
+    pub null_move_reduction: u16,
+    pub null_move_depth_divisor: u16,
+    /**
+     * From this depth on, a null-move cutoff is only trusted after a normal search
+     * (with null moves disabled) fails high as well. Guards against zugzwang.
+     */
+    pub null_move_verification_depth: u16,
+    /**
+     * Late move reductions: quiet moves ordered after the hash move and the killers
+     * are searched with less depth first, and only again with the full depth if they
+     * turn out to be better than the best move so far.
+     */
+    pub late_move_reductions: bool,
+    pub lmr_min_depth: u16,
+    /**
+     * How many moves are always searched to the full depth.
+     */
+    pub lmr_min_move_index: usize,
+    /**
+     * The reduction is `lmr_base + ln(depth) * ln(move index) / lmr_divisor`.
+     */
+    pub lmr_base: f64,
+    pub lmr_divisor: f64,
+    /**
+     * Late move pruning: up to this depth, quiet moves after the first
+     * `lmp_base + depth * depth` ones aren't searched at all.
+     */
+    pub late_move_pruning: bool,
+    pub lmp_max_depth: u16,
+    pub lmp_base: usize,
+    /**
+     * From this depth on, the root is searched with a window of `aspiration_delta`
+     * around the last iteration's score, doubled every time the score falls outside of it.
+     */
+    pub aspiration_min_depth: u16,
+    pub aspiration_delta: i32,
+    /**
+     * Moves giving check, and the only legal reply to a check, are searched one ply deeper.
+     */
+    pub check_extensions: bool,
+    /**
+     * The hash move is searched one ply deeper if every other move scores at least
+     * `singular_margin * depth` below it, from this depth on.
+     */
+    pub singular_extensions: bool,
+    pub singular_min_depth: u16,
+    pub singular_margin: i32,
+    /**
+     * How many plies a line can be extended by in total, so the tree stays bounded.
+     */
+    pub max_line_extensions: u16,
+    /**
+     * Reverse futility pruning: up to this depth, a node whose static evaluation is at least
+     * `rfp_margin * depth` above beta fails high right away.
+     */
+    pub reverse_futility_pruning: bool,
+    pub rfp_max_depth: u16,
+    pub rfp_margin: i32,
+    /**
+     * Futility pruning: up to this depth, quiet moves aren't searched if the static evaluation
+     * plus `futility_base + futility_margin * depth` doesn't reach alpha.
+     */
+    pub futility_pruning: bool,
+    pub futility_max_depth: u16,
+    pub futility_base: i32,
+    pub futility_margin: i32,
+    /**
+     * Razoring: up to this depth, a node whose static evaluation is more than
+     * `razoring_base + razoring_margin * depth` below alpha is only searched by the quiescence search.
+     */
+    pub razoring: bool,
+    pub razoring_max_depth: u16,
+    pub razoring_base: i32,
+    pub razoring_margin: i32,
+}
+
+impl Default for SearchParams {
+    fn default() -> Self {
+        Self {
+            null_move_pruning: true,
+            null_move_min_depth: 3,
+            null_move_reduction: 2,
+            null_move_depth_divisor: 4,
+            null_move_verification_depth: 10,
+            late_move_reductions: true,
+            lmr_min_depth: 3,
+            lmr_min_move_index: 3,
+            lmr_base: 0.75,
+            lmr_divisor: 2.25,
+            late_move_pruning: true,
+            lmp_max_depth: 3,
+            lmp_base: 3,
+            aspiration_min_depth: 4,
+            aspiration_delta: 25,
+            check_extensions: true,
+            singular_extensions: true,
+            singular_min_depth: 8,
+            singular_margin: 2,
+            max_line_extensions: 16,
+            reverse_futility_pruning: true,
+            rfp_max_depth: 3,
+            rfp_margin: 100,
+            futility_pruning: true,
+            futility_max_depth: 3,
+            futility_base: 100,
+            futility_margin: 100,
+            razoring: true,
+            razoring_max_depth: 3,
+            razoring_base: 300,
+            razoring_margin: 200,
+        }
+    }
+}
+
+impl SearchParams {
+    /**
+     * The reduction of every depth and move index, `table[depth][index]`.
+     * Built once per search instead of taking logarithms in every node.
+     */
+    pub fn reduction_table(&self) -> Vec<Vec<u16>> {
+        let mut table = vec![vec![0; MAX_REDUCED_MOVES]; MAX_DEPTH as usize + 1];
+        for (depth, row) in table.iter_mut().enumerate().skip(1) {
+            for (index, reduction) in row.iter_mut().enumerate().skip(1) {
+                let r = self.lmr_base
+                    + (depth as f64).ln() * (index as f64).ln() / self.lmr_divisor;
+                *reduction = r.max(0.0) as u16;
+            }
+        }
+        table
+    }
+
+    /**
+     * How many quiet moves are searched at `depth` before late move pruning kicks in.
+     */
+    pub fn late_move_count(&self, depth: u16) -> usize {
+        self.lmp_base + (depth as usize).pow(2)
+    }
+
+    pub fn futility_margin(&self, depth: u16) -> i32 {
+        self.futility_base + self.futility_margin * depth as i32
+    }
+
+    pub fn razoring_margin(&self, depth: u16) -> i32 {
+        self.razoring_base + self.razoring_margin * depth as i32
+    }
+}
@@ -0,0 +1,216 @@
+use chess::Board;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::uci::uci::DepthData;
+
+pub const MAX_SKILL_LEVEL: u8 = 20;
+/**
+ * The UCI_Elo range, mapped onto the skill levels 0 to 20.
+ */
+pub const MIN_ELO: u16 = 800;
+pub const MAX_ELO: u16 = 2400;
+pub const DEFAULT_ELO: u16 = 1500;
+/**
+ * How many of the best moves a weakened bot chooses from.
+ */
+pub const SKILL_MULTI_PV: usize = 4;
+
+/**
+ * Playing weaker on purpose, for opponents of every level.
+ *
+ * Below the top level the search is cut short (depth and nodes), the evaluation gets
+ * some noise, and the move is picked among the best few with lower levels
+ * picking worse moves more often. Like a human that doesn't look as far and sometimes
+ * misjudges a position, instead of one that plays perfectly and blunders at random.
+ */
+#[derive(Clone, Debug)]
+pub struct Skill {
+    /**
+     * "Skill Level", 0 to 20. 20 is full strength.
+     */
+    pub level: u8,
+    /**
+     * "UCI_LimitStrength", plays at `elo` instead of `level`.
+     */
+    pub limit_strength: bool,
+    /**
+     * "UCI_Elo"
+     */
+    pub elo: u16,
+    /**
+     * "Seed", the same seed plays the same moves in the same positions, so games can be replayed.
+     * 0 picks a new one every search.
+     */
+    pub seed: u64,
+    search_seed: u64,
+}
+
+impl Default for Skill {
+    fn default() -> Self {
+        Self {
+            level: MAX_SKILL_LEVEL,
+            limit_strength: false,
+            elo: DEFAULT_ELO,
+            seed: 0,
+            search_seed: 0,
+        }
+    }
+}
+
+impl Skill {
+    /**
+     * The level to play at, with fractions from UCI_Elo. None at full strength.
+     */
+    pub fn effective_level(&self) -> Option<f64> {
+        let level = if self.limit_strength {
+            let elo = self.elo.clamp(MIN_ELO, MAX_ELO);
+            (elo - MIN_ELO) as f64 / (MAX_ELO - MIN_ELO) as f64 * MAX_SKILL_LEVEL as f64
+        } else {
+            self.level.min(MAX_SKILL_LEVEL) as f64
+        };
+        (level < MAX_SKILL_LEVEL as f64).then_some(level)
+    }
+
+    /**
+     * Has to be called before every search, picks the randomness of the search.
+     * Helpers get a copy instead, so every thread adds the same noise to the shared table.
+     */
+    pub fn new_search(&mut self, board: &Board) {
+        self.search_seed = if self.seed == 0 {
+            rand::thread_rng().gen()
+        } else {
+            self.seed ^ board.get_hash()
+        };
+    }
+
+    /**
+     * Deepest the weakened bot searches, level 0 only looks at its own moves.
+     */
+    pub fn depth_limit(level: f64) -> u16 {
+        1 + (level / 2.0) as u16
+    }
+
+    pub fn node_limit(level: f64) -> u64 {
+        (1000.0 * 1.5_f64.powf(level)) as u64
+    }
+
+    /**
+     * How far off (in centipawns, both ways) the evaluation can be.
+     */
+    fn noise_amplitude(level: f64) -> i32 {
+        ((MAX_SKILL_LEVEL as f64 - level) * 8.0) as i32
+    }
+
+    /**
+     * The noise added to the static evaluation of `board`. The same position gets
+     * the same noise for the whole search, so the transposition table stays consistent.
+     */
+    pub fn noise(&self, board: &Board) -> i32 {
+        let Some(level) = self.effective_level() else {
+            return 0;
+        };
+        let amplitude = Self::noise_amplitude(level);
+        if amplitude == 0 {
+            return 0;
+        }
+        // a cheap hash of the position and the seed.
+        let hash = (board.get_hash() ^ self.search_seed).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        ((hash >> 32) % (2 * amplitude as u64 + 1)) as i32 - amplitude
+    }
+
+    /**
+     * Picks the move to play among `lines` (best first). Every line is picked with a probability
+     * that falls off with how much worse it is than the best, faster on higher levels.
+     */
+    pub fn pick<'a>(&self, lines: &'a [DepthData], level: f64) -> Option<&'a DepthData> {
+        let best = lines.first()?.eval;
+        let temperature = 10.0 + (MAX_SKILL_LEVEL as f64 - level) * 12.0;
+        let weights: Vec<f64> = lines
+            .iter()
+            .map(|line| (-((best - line.eval) as f64) / temperature).exp())
+            .collect();
+
+        let mut rng = StdRng::seed_from_u64(self.search_seed);
+        let mut choice = rng.gen::<f64>() * weights.iter().sum::<f64>();
+        for (line, weight) in lines.iter().zip(&weights) {
+            if choice < *weight {
+                return Some(line);
+            }
+            choice -= weight;
+        }
+        lines.last()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Skill, MAX_ELO, MIN_ELO};
+    use crate::tables::transposition::Bound;
+    use crate::types::score;
+    use crate::uci::uci::DepthData;
+    use chess::Board;
+
+    fn line(eval: i32) -> DepthData {
+        DepthData {
+            depth: 1,
+            multi_pv: 1,
+            eval,
+            best_move: None,
+            pv: Vec::new(),
+            bound: Bound::Exact,
+            node_count: 0,
+            ms_passed: 0,
+            hashfull: 0,
+        }
+    }
+
+    #[test]
+    fn maps_elo_to_levels() {
+        let mut skill = Skill::default();
+        assert_eq!(skill.effective_level(), None);
+
+        skill.level = 5;
+        assert_eq!(skill.effective_level(), Some(5.0));
+
+        skill.limit_strength = true;
+        skill.elo = MIN_ELO - 100;
+        assert_eq!(skill.effective_level(), Some(0.0));
+        skill.elo = (MIN_ELO + MAX_ELO) / 2;
+        assert_eq!(skill.effective_level(), Some(10.0));
+        skill.elo = MAX_ELO;
+        assert_eq!(skill.effective_level(), None);
+    }
+
+    #[test]
+    fn the_same_seed_picks_the_same_move() {
+        let board = Board::default();
+        let lines = [line(50), line(40), line(-20), line(-300)];
+        let picks = |seed: u64| {
+            let mut skill = Skill {
+                level: 0,
+                seed,
+                ..Skill::default()
+            };
+            skill.new_search(&board);
+            (skill.pick(&lines, 0.0).unwrap().eval, skill.noise(&board))
+        };
+        assert_eq!(picks(7), picks(7));
+        assert!(picks(7).1.abs() <= 160);
+    }
+
+    #[test]
+    fn never_passes_up_a_mate() {
+        let mut skill = Skill {
+            level: 10,
+            seed: 3,
+            ..Skill::default()
+        };
+        let lines = [line(score::mate_in(3)), line(200), line(150)];
+        for seed in 1..100 {
+            skill.seed = seed;
+            skill.new_search(&Board::default());
+            assert_eq!(skill.pick(&lines, 10.0).unwrap().eval, score::mate_in(3));
+        }
+    }
+}
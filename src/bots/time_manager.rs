@@ -0,0 +1,287 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chess::Color;
+use vampirc_uci::UciTimeControl;
+
+/**
+ * Time we keep in reserve for the GUI/OS latency of every move.
+ */
+const MOVE_OVERHEAD: Duration = Duration::from_millis(20);
+/**
+ * How many moves we assume are left when the GUI doesn't send "movestogo".
+ */
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+
+/**
+ * Decides how long a single "go" is allowed to think.
+ *
+ * The soft limit is checked between iterations (don't start a depth we can't finish),
+ * the hard limit is checked inside the search and aborts it.
+ * No limits means the search only ends by depth or "stop".
+ *
+ * While pondering, the limits don't count yet: they start on "ponderhit".
+ */
+#[derive(Clone, Debug)]
+pub struct TimeManager {
+    /**
+     * When "go" came, for the time and nps reported to the GUI.
+     */
+    start: Instant,
+    /**
+     * When the limits started counting, the same as `start` unless pondering.
+     */
+    limits_start: Instant,
+    soft_limit: Option<Duration>,
+    hard_limit: Option<Duration>,
+    infinite: bool,
+    /**
+     * Set while "go ponder" searches on the opponent's time, cleared by "ponderhit".
+     */
+    pondering: Option<Arc<AtomicBool>>,
+}
+
+impl Default for TimeManager {
+    fn default() -> Self {
+        Self::unlimited()
+    }
+}
+
+impl TimeManager {
+    /**
+     * No time limits, e.g. "go depth x".
+     */
+    pub fn unlimited() -> Self {
+        let start = Instant::now();
+        Self {
+            start,
+            limits_start: start,
+            soft_limit: None,
+            hard_limit: None,
+            infinite: false,
+            pondering: None,
+        }
+    }
+
+    /**
+     * "go infinite", no time limits and the result is held back until "stop".
+     */
+    pub fn infinite() -> Self {
+        Self {
+            infinite: true,
+            ..Self::unlimited()
+        }
+    }
+
+    /**
+     * "go movetime x", searches exactly x milliseconds (minus the overhead).
+     */
+    pub fn move_time(move_time: Duration) -> Self {
+        let limit = move_time
+            .saturating_sub(MOVE_OVERHEAD)
+            .max(Duration::from_millis(1));
+        let start = Instant::now();
+        Self {
+            start,
+            limits_start: start,
+            soft_limit: Some(limit),
+            hard_limit: Some(limit),
+            infinite: false,
+            pondering: None,
+        }
+    }
+
+    /**
+     * "go wtime x btime x winc x binc x movestogo x"
+     *
+     * Splits the remaining clock over the moves left until the next time control,
+     * plus most of the increment. The hard limit lets a search overshoot the soft one
+     * but never spends more than a fraction of what's left on the clock.
+     */
+    pub fn time_left(time_left: Duration, increment: Duration, moves_to_go: Option<u32>) -> Self {
+        let moves_to_go = moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+        let available = time_left
+            .saturating_sub(MOVE_OVERHEAD)
+            .max(Duration::from_millis(1));
+
+        let hard_limit = if moves_to_go == 1 {
+            available
+        } else {
+            available * 4 / 5
+        };
+        let soft_limit = (available / moves_to_go + increment * 3 / 4).min(hard_limit);
+        let hard_limit = (soft_limit * 3).min(hard_limit);
+
+        let start = Instant::now();
+        Self {
+            start,
+            limits_start: start,
+            soft_limit: Some(soft_limit),
+            hard_limit: Some(hard_limit),
+            infinite: false,
+            pondering: None,
+        }
+    }
+
+    /**
+     * Reads the UCI time control for the side that is about to move.
+     */
+    pub fn from_time_control(time_control: &UciTimeControl, side_to_move: Color) -> Self {
+        match time_control {
+            UciTimeControl::Ponder | UciTimeControl::Infinite => Self::infinite(),
+            UciTimeControl::MoveTime(move_time) => Self::move_time(to_std_duration(move_time)),
+            UciTimeControl::TimeLeft {
+                white_time,
+                black_time,
+                white_increment,
+                black_increment,
+                moves_to_go,
+            } => {
+                let (time, increment) = match side_to_move {
+                    Color::White => (white_time, white_increment),
+                    Color::Black => (black_time, black_increment),
+                };
+                match time {
+                    Some(time) => Self::time_left(
+                        to_std_duration(time),
+                        increment.as_ref().map(to_std_duration).unwrap_or_default(),
+                        moves_to_go.map(|moves| moves as u32),
+                    ),
+                    None => Self::unlimited(),
+                }
+            }
+        }
+    }
+
+    /**
+     * "go ponder": the limits of `self` only start once `pondering` is cleared by "ponderhit".
+     */
+    pub fn ponder(self, pondering: Arc<AtomicBool>) -> Self {
+        Self {
+            pondering: Some(pondering),
+            ..self
+        }
+    }
+
+    pub fn is_infinite(&self) -> bool {
+        self.infinite
+    }
+
+    pub fn is_pondering(&self) -> bool {
+        self.pondering.is_some()
+    }
+
+    /**
+     * Turns a pondering search into a normal one after "ponderhit", our clock starts now.
+     * The time already spent pondering still counts in `elapsed`.
+     */
+    pub fn check_ponderhit(&mut self) {
+        if self
+            .pondering
+            .as_ref()
+            .is_some_and(|pondering| !pondering.load(Ordering::Relaxed))
+        {
+            self.pondering = None;
+            self.limits_start = Instant::now();
+        }
+    }
+
+    /**
+     * Time since "go", pondering included.
+     */
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    pub fn soft_limit_reached(&self) -> bool {
+        !self.is_pondering()
+            && self.soft_limit.is_some_and(|limit| self.limits_start.elapsed() >= limit)
+    }
+
+    pub fn hard_limit_reached(&self) -> bool {
+        !self.is_pondering()
+            && self.hard_limit.is_some_and(|limit| self.limits_start.elapsed() >= limit)
+    }
+}
+
+/**
+ * The UCI durations can be negative (some GUIs send a negative clock when flagging).
+ */
+fn to_std_duration(duration: &vampirc_uci::Duration) -> Duration {
+    duration.to_std().unwrap_or(Duration::ZERO)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TimeManager, MOVE_OVERHEAD};
+    use chess::Color;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+    use vampirc_uci::UciTimeControl;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn limits(time_manager: &TimeManager) -> (Duration, Duration) {
+        (time_manager.soft_limit.unwrap(), time_manager.hard_limit.unwrap())
+    }
+
+    #[test]
+    fn splits_the_clock_over_the_moves_to_go() {
+        // the last move before the time control can use everything but the overhead.
+        let last_move = TimeManager::time_left(ms(10_000) + MOVE_OVERHEAD, Duration::ZERO, Some(1));
+        assert_eq!(limits(&last_move), (ms(10_000), ms(10_000)));
+
+        let ten_moves = TimeManager::time_left(ms(10_000) + MOVE_OVERHEAD, Duration::ZERO, Some(10));
+        assert_eq!(limits(&ten_moves), (ms(1_000), ms(3_000)));
+
+        // 30 moves to go without "movestogo", and most of the increment on top.
+        let increment = TimeManager::time_left(ms(30_000) + MOVE_OVERHEAD, ms(1_000), None);
+        assert_eq!(limits(&increment), (ms(1_750), ms(5_250)));
+    }
+
+    #[test]
+    fn never_spends_more_than_the_clock() {
+        let (soft, hard) = limits(&TimeManager::time_left(ms(10), ms(5_000), Some(1)));
+        assert!(soft <= hard && hard <= ms(1));
+
+        // a flagging GUI can send a negative clock.
+        let negative = UciTimeControl::TimeLeft {
+            white_time: Some(vampirc_uci::Duration::milliseconds(60_000)),
+            black_time: Some(vampirc_uci::Duration::milliseconds(-50)),
+            white_increment: None,
+            black_increment: None,
+            moves_to_go: None,
+        };
+        let (soft, hard) = limits(&TimeManager::from_time_control(&negative, Color::Black));
+        assert!(soft > Duration::ZERO && soft <= hard && hard <= ms(1));
+        let (soft, _) = limits(&TimeManager::from_time_control(&negative, Color::White));
+        assert_eq!(soft, (ms(60_000) - MOVE_OVERHEAD) / 30);
+    }
+
+    #[test]
+    fn move_time_keeps_the_overhead() {
+        let limit = ms(1_000) - MOVE_OVERHEAD;
+        assert_eq!(limits(&TimeManager::move_time(ms(1_000))), (limit, limit));
+        assert_eq!(limits(&TimeManager::move_time(ms(5))), (ms(1), ms(1)));
+    }
+
+    #[test]
+    fn ponderhit_starts_the_limits_but_not_the_reported_time() {
+        let pondering = Arc::new(AtomicBool::new(true));
+        let mut time_manager = TimeManager::move_time(ms(1_000)).ponder(Arc::clone(&pondering));
+        thread::sleep(ms(30));
+        time_manager.check_ponderhit();
+        assert!(time_manager.is_pondering());
+
+        pondering.store(false, Ordering::Relaxed);
+        time_manager.check_ponderhit();
+        assert!(!time_manager.is_pondering());
+        assert!(time_manager.elapsed() >= ms(30));
+        assert!(time_manager.limits_start.elapsed() < time_manager.elapsed());
+    }
+}
@@ -1,7 +1,7 @@
 use chess::ChessMove;
 use colored::*;
 
-pub fn print_board_from_fen(fen: &str, targets: &Vec<ChessMove>, moves: &Vec<ChessMove>) {
+pub fn print_board_from_fen(fen: &str, targets: &[ChessMove], moves: &[ChessMove]) {
     dbg!(targets.len(), moves.len());
 
     let fen_parts: Vec<&str> = fen.split(' ').collect();
@@ -23,7 +23,7 @@ pub fn print_board_from_fen(fen: &str, targets: &Vec<ChessMove>, moves: &Vec<Che
                 '1'..='8' => {
                     let num_spaces = character.to_digit(10).unwrap();
                     for _ in 0..num_spaces {
-                        let piece = format!(" . ");
+                        let piece = " . ".to_string();
                         if moves
                             .iter()
                             .any(|chess_move| chess_move.get_dest().to_index() == square_index)
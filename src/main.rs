@@ -2,21 +2,26 @@ extern crate vampirc_uci;
 
 use mimalloc::MiMalloc;
 
-use chess::{Board, BoardStatus, Game};
+use chess::{Board, ChessMove};
 use std::str::FromStr;
-use std::time::{Duration, Instant};
 use uci::conversion::uci_move_to_chess_move;
-use vampirc_uci::{parse, UciInfoAttribute, UciMessage, UciTimeControl};
+use vampirc_uci::{parse, UciMessage};
 
 use std::io::stdin;
-use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
-use std::sync::{Arc, RwLock};
-use std::thread;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
 
-use crate::bots::basic_bot::BasicBot;
+use crate::bots::basic_bot::{BasicBot, MAX_DEPTH};
 use crate::bots::bot_traits::Search;
+use crate::bots::search_limits::SearchLimits;
+use crate::bots::time_manager::TimeManager;
+use crate::tables::transposition::TranspositionTable;
+use crate::uci::options::{parse_set_option, EngineOption, OptionValue, DEFAULT_HASH_MB, OPTIONS};
 use crate::uci::conversion;
 
+pub mod bench;
 pub mod bots;
 pub mod fen;
 pub mod moves;
@@ -27,166 +32,312 @@ pub mod uci;
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
 
-fn output_thread(out: UciMessage, bot: &mut BasicBot, toggle_ready_ok: &Arc<RwLock<bool>>) {
+/**
+ * Blocks until the running search (if any) has printed its "bestmove".
+ */
+fn wait_for_search(search_thread: &mut Option<JoinHandle<()>>) {
+    if let Some(handle) = search_thread.take() {
+        handle.join().expect("Search thread panicked.");
+    }
+}
+
+/**
+ * Ends the running search (if any), like "stop" would, and waits for its "bestmove".
+ */
+fn stop_search(
+    search_thread: &mut Option<JoinHandle<()>>,
+    stop: &AtomicBool,
+    pondering: &AtomicBool,
+) {
+    if search_thread.is_some() {
+        stop.store(true, Ordering::Relaxed);
+        pondering.store(false, Ordering::Relaxed);
+    }
+    wait_for_search(search_thread);
+}
+
+/**
+ * A parsed UCI command. vampirc-uci reads "go ponder wtime ..." as a plain "go" with a clock
+ * and forgets the "ponder", so it's read from the line and kept next to the message.
+ */
+struct Command {
+    message: UciMessage,
+    ponder: bool,
+}
+
+fn set_option(bot: &mut BasicBot, option: EngineOption, value: OptionValue) {
+    match (option, value) {
+        (EngineOption::Hash, OptionValue::Spin(mb)) => {
+            bot.tt_table = Arc::new(TranspositionTable::new(mb as usize * 1024 * 1024));
+        }
+        (EngineOption::ClearHash, _) => bot.tt_table.clear(),
+        (EngineOption::Threads, OptionValue::Spin(threads)) => bot.threads = threads as usize,
+        (EngineOption::MultiPv, OptionValue::Spin(lines)) => bot.multi_pv = lines as usize,
+        (EngineOption::Ponder, OptionValue::Check(value)) => bot.ponder = value,
+        (EngineOption::NullMove, OptionValue::Check(value)) => bot.params.null_move_pruning = value,
+        (EngineOption::Contempt, OptionValue::Spin(contempt)) => bot.contempt = contempt as i32,
+        (EngineOption::SkillLevel, OptionValue::Spin(level)) => bot.skill.level = level as u8,
+        (EngineOption::LimitStrength, OptionValue::Check(value)) => bot.skill.limit_strength = value,
+        (EngineOption::Elo, OptionValue::Spin(elo)) => bot.skill.elo = elo as u16,
+        (EngineOption::Seed, OptionValue::Spin(seed)) => bot.skill.seed = seed as u64,
+        (option, value) => unreachable!("{:?} can't be set to {:?}", option, value),
+    }
+}
+
+fn output_thread(
+    command: Command,
+    bot: &Arc<Mutex<BasicBot>>,
+    stop: &Arc<AtomicBool>,
+    pondering: &Arc<AtomicBool>,
+    search_thread: &mut Option<JoinHandle<()>>,
+) {
+    let Command {
+        message: out,
+        ponder,
+    } = command;
     match out {
         UciMessage::Uci => {
             println!("id name Cirno");
             println!("id author twoleaflotus");
+            for option in &OPTIONS {
+                println!("{}", UciMessage::Option(option.to_config()));
+            }
             println!("{}", UciMessage::UciOk);
         }
 
+        // answered right away, even in the middle of a search.
         UciMessage::IsReady => {
-            *toggle_ready_ok.write().unwrap() = true;
+            println!("{}", UciMessage::ReadyOk);
+        }
+
+        // already raised by the main thread, raised again in case a "go" queued before it cleared it.
+        UciMessage::Stop => {
+            stop.store(true, Ordering::Relaxed);
+            pondering.store(false, Ordering::Relaxed);
+        }
+
+        // the opponent played the move we pondered on, the search goes on with our clock running.
+        UciMessage::PonderHit => {
+            pondering.store(false, Ordering::Relaxed);
         }
 
         UciMessage::Quit => {
+            stop_search(search_thread, stop, pondering);
             std::process::exit(0);
         }
 
+        UciMessage::SetOption {
+            ref name,
+            ref value,
+        } => {
+            // options change how the bot searches, so the search ends first.
+            stop_search(search_thread, stop, pondering);
+            match parse_set_option(name, value.as_deref()) {
+                Ok((option, value)) => set_option(&mut bot.lock().unwrap(), option, value),
+                Err(error) => println!("info string {}", error),
+            }
+        }
+
         UciMessage::Position {
             startpos,
             fen,
             moves,
         } => {
             let board = if startpos {
-                Some(Board::default())
+                Some((Board::default(), 0))
             } else {
-                fen.and_then(|fen| Board::from_str(fen.0.as_str()).ok())
+                fen.and_then(|fen| {
+                    // chess' Board doesn't keep the halfmove clock, the fifty-move rule needs it.
+                    let halfmove_clock = fen.0.split_whitespace().nth(4);
+                    let halfmove_clock = halfmove_clock.and_then(|clock| clock.parse().ok());
+                    Board::from_str(fen.0.as_str())
+                        .ok()
+                        .map(|board| (board, halfmove_clock.unwrap_or(0)))
+                })
             };
-            if let Some(board) = board {
-                let mut new_board = board.clone();
-                for uci_move in moves {
-                    if let Ok(chess_move) = uci_move_to_chess_move(&uci_move) {
-                        if new_board.status() == BoardStatus::Ongoing && new_board.legal(chess_move)
-                        {
-                            let temp_board = new_board.make_move_new(chess_move);
-                            new_board = temp_board;
-                        }
-                    }
-                }
-                bot.change_board(&new_board);
+            if let Some((board, halfmove_clock)) = board {
+                stop_search(search_thread, stop, pondering);
+                let mut bot = bot.lock().unwrap();
+                let moves: Vec<ChessMove> = moves
+                    .iter()
+                    .filter_map(|uci_move| uci_move_to_chess_move(uci_move).ok())
+                    .collect();
+                bot.set_position(&board, halfmove_clock, &moves);
             }
         }
 
+        // nothing from the last game (or test position) may change how the next one is searched.
+        UciMessage::UciNewGame => {
+            stop_search(search_thread, stop, pondering);
+            bot.lock().unwrap().new_game();
+        }
+
         // engine responsibilities, so "go" has to be here
         UciMessage::Go {
             time_control,
             search_control,
         } => {
-            if let Some(time_control) = time_control {
-                match time_control {
-                    // the act of thinking during the opponent's turn.
-                    // we have separate threads so this should be easy to implement.
-                    //      however, i don't really see a gain with
-                    //      pondering (with the techniques rn), so no thanks.
-                    //
-                    // (opinion)
-                    // also pondering can only be worth it *if*
-                    //      the engine & player is on the same level.
-                    //      since pondering searches through the tree and does a null-move (?)
-                    //      and it only becomes worth if the player plays the expected move.
-                    // https://www.chessprogramming.org/Pondering
-                    UciTimeControl::Ponder => (),
-                    UciTimeControl::Infinite => (),
-                    UciTimeControl::MoveTime(_time) => (),
-                    UciTimeControl::TimeLeft { .. } => (),
-                };
+            // "go ponder" thinks on the opponent's time, in the position after the move we expect.
+            // it searches without limits until "ponderhit" (the opponent played that move) starts
+            // our clock and the search goes on with what it found so far, or until "stop".
+            // https://www.chessprogramming.org/Pondering
+            //
+            // the Ponder option only tells us whether the GUI will send "go ponder", and
+            // whether it wants a move to ponder on in "bestmove".
+            stop_search(search_thread, stop, pondering);
+
+            let search_control = search_control.unwrap_or_default();
+            let side_to_move = bot.lock().unwrap().board.side_to_move();
+            let time_manager = match &time_control {
+                Some(time_control) => TimeManager::from_time_control(time_control, side_to_move),
+                // a "go" without any limit searches until "stop".
+                None if search_control.depth.is_none()
+                    && search_control.nodes.is_none()
+                    && search_control.mate.is_none() =>
+                {
+                    TimeManager::infinite()
+                }
+                None => TimeManager::unlimited(),
+            };
+            pondering.store(ponder, Ordering::Relaxed);
+            let time_manager = if ponder {
+                time_manager.ponder(Arc::clone(pondering))
+            } else {
+                time_manager
+            };
+            let depth = search_control
+                .depth
+                .map_or(MAX_DEPTH, |depth| (depth as u16).clamp(1, MAX_DEPTH));
+            bot.lock().unwrap().limits = SearchLimits {
+                search_moves: search_control
+                    .search_moves
+                    .iter()
+                    .filter_map(|uci_move| uci_move_to_chess_move(uci_move).ok())
+                    .collect(),
+                nodes: search_control.nodes,
+                mate: search_control.mate.map(u16::from),
             };
-            if let Some(search_control) = search_control {
-                if let Some(depth) = search_control.depth {
-                    let start_time = Instant::now();
-                    let (eval, chess_move) = bot.search(depth as u16);
-                    let end_time = Instant::now();
-                    println!("Search duration: {:?}", end_time.duration_since(start_time));
-                    let best_uci_move = conversion::chess_move_to_uci_move(&chess_move);
-
-                    let depth_data = bot.uci.get_depth_data();
-
-                    for index in (0..depth_data.len()).rev() {
-                        let data = &depth_data[index];
-
-                        let mut info_vec: Vec<UciInfoAttribute> = vec![];
-                        info_vec.reserve(3);
-
-                        if let Some(chess_move) = data.best_move {
-                            let chess_move = conversion::chess_move_to_uci_move(&chess_move);
-                            info_vec.push(UciInfoAttribute::Pv(vec![chess_move]));
-                        };
-                        info_vec.push(UciInfoAttribute::Depth(data.depth as u8));
-                        info_vec.push(UciInfoAttribute::Nodes(data.node_count as u64));
-
-                        println!("{}", UciMessage::Info(info_vec));
-                    }
-                    let best_move = UciMessage::best_move(best_uci_move);
-                    println!("{}", best_move);
 
+            // the search gets its own thread so "stop" and "isready" are still read meanwhile.
+            stop.store(false, Ordering::Relaxed);
+            let bot = Arc::clone(bot);
+            *search_thread = Some(thread::spawn(move || {
+                let mut bot = bot.lock().unwrap();
+                // checkmate or stalemate, but every "go" still has to be answered.
+                let Some((_eval, chess_move)) = bot.search(depth, time_manager) else {
+                    println!("bestmove 0000");
                     bot.reset();
+                    return;
+                };
+                let best_uci_move = conversion::chess_move_to_uci_move(&chess_move);
+                // a weakened bot doesn't always play the first line.
+                let ponder_move = bot
+                    .uci
+                    .lines
+                    .iter()
+                    .find(|line| line.best_move == Some(chess_move))
+                    .and_then(|line| line.ponder_move())
+                    .filter(|_| bot.ponder);
+                match ponder_move {
+                    Some(ponder_move) => println!(
+                        "{}",
+                        UciMessage::best_move_with_ponder(
+                            best_uci_move,
+                            conversion::chess_move_to_uci_move(&ponder_move)
+                        )
+                    ),
+                    None => println!("{}", UciMessage::best_move(best_uci_move)),
                 }
-                
-            };
+
+                bot.reset();
+            }));
         }
         _ => {}
     }
-    if *toggle_ready_ok.read().unwrap() == true {
-        println!("{}", UciMessage::ReadyOk);
-    }
-    *toggle_ready_ok.write().unwrap() = false;
 }
 
 fn main() {
-    let (output_tx, output_rx): (Sender<UciMessage>, Receiver<UciMessage>) = mpsc::channel();
-    let (input_tx, input_rx): (Sender<UciMessage>, Receiver<UciMessage>) = mpsc::channel();
-    let toggle_ready_ok = Arc::new(RwLock::new(false));
+    if std::env::args().nth(1).as_deref() == Some("bench") {
+        let depth = std::env::args()
+            .nth(2)
+            .and_then(|depth| depth.parse().ok())
+            .map_or(bench::DEFAULT_BENCH_DEPTH, |depth: u16| depth.clamp(1, MAX_DEPTH));
+        bench::bench(depth);
+        return;
+    }
+
+    let (output_tx, output_rx): (Sender<Command>, Receiver<Command>) = mpsc::channel();
+    let (input_tx, input_rx): (Sender<Command>, Receiver<Command>) = mpsc::channel();
 
     // INPUT
     thread::spawn(move || loop {
         let mut input = String::new();
-        stdin().read_line(&mut input).expect("Failed to read line");
+        let bytes_read = stdin().read_line(&mut input).expect("Failed to read line");
 
-        let uci = parse(input.as_str());
-        for command in uci {
+        // the GUI closed our stdin, nothing else is coming.
+        let uci = if bytes_read == 0 {
+            vec![UciMessage::Quit]
+        } else {
+            parse(input.as_str())
+        };
+        let ponder = input
+            .split_whitespace()
+            .any(|word| word.eq_ignore_ascii_case("ponder"));
+        for message in uci {
+            let ponder = ponder && matches!(message, UciMessage::Go { .. });
             input_tx
-                .send(command)
+                .send(Command { message, ponder })
                 .expect("Failed to send input to main thread.")
         }
     });
 
+    let bot = BasicBot::new(&Board::default(), DEFAULT_HASH_MB * 1024 * 1024);
+    // the search thread holds the bot's lock, so the flags are shared separately.
+    let stop = Arc::clone(&bot.stop);
+    let pondering = Arc::clone(&bot.pondering);
+
     // OUTPUT
-    thread::spawn(move || {
-        let board = Board::default();
-        let mut bot = BasicBot::new(&board, 50 * 1000 * 1000);
-
-        loop {
-            thread::sleep(Duration::from_millis(100));
-            match output_rx.try_recv() {
-                Ok(out) => output_thread(out, &mut bot, &toggle_ready_ok),
-                Err(err) => match err {
-                    TryRecvError::Disconnected => panic!("Disconnected from the main thread!"),
-                    _ => {}
-                },
+    {
+        let stop = Arc::clone(&stop);
+        let pondering = Arc::clone(&pondering);
+        thread::spawn(move || {
+            let bot = Arc::new(Mutex::new(bot));
+            let mut search_thread = None;
+
+            for out in output_rx {
+                output_thread(out, &bot, &stop, &pondering, &mut search_thread);
             }
-        }
-    });
+            panic!("Disconnected from the main thread!");
+        });
+    }
 
     loop {
         // this part might seem useless but its not.
-        let uci_message = input_rx
+        let command = input_rx
             .recv()
             .expect("Failed to recieve from input thread.");
 
-        thread::sleep(Duration::from_millis(100));
+        // the output thread can be busy waiting for a search, so the flags the search polls
+        // are set here, before the command queues behind others.
+        match command.message {
+            UciMessage::Stop | UciMessage::Quit => {
+                stop.store(true, Ordering::Relaxed);
+                pondering.store(false, Ordering::Relaxed);
+            }
+            UciMessage::PonderHit => pondering.store(false, Ordering::Relaxed),
+            _ => {}
+        }
 
-        match uci_message {
+        match command.message {
             UciMessage::Uci
             | UciMessage::IsReady
+            | UciMessage::SetOption { .. }
+            | UciMessage::UciNewGame
             | UciMessage::Position { .. }
             | UciMessage::Go { .. }
+            | UciMessage::PonderHit
             | UciMessage::Stop
-            | UciMessage::Quit => output_tx.send(uci_message),
-
-            // not supported, use position startpos moves e2e4 ... instead.
-            // https://stackoverflow.com/questions/56528420/basic-questions-on-uci-engine-ucinewgame-and-multiple-clients
-            UciMessage::UciNewGame => Ok(()),
+            | UciMessage::Quit => output_tx.send(command),
             _ => Ok(()),
         }
         .expect("Main thread can't send to output/process thread");
@@ -1,2 +1,3 @@
-pub mod move_gen;
+pub mod move_picker;
+pub mod see;
 pub mod user_move;
@@ -1,25 +0,0 @@
-use chess::{Board, ChessMove, MoveGen, EMPTY};
-
-/**
- * A tuple is a bad idea isn't it?
- *
- * Returns (capture_moves, non_capture_moves)
- */
-pub fn generate_moves(board: &Board) -> Vec<ChessMove> {
-    let mut legal_iterable = MoveGen::new_legal(&board);
-    let targets = board.color_combined(!board.side_to_move());
-
-    let mut moves: Vec<_> = vec![];
-
-    legal_iterable.set_iterator_mask(*targets);
-    for mov in &mut legal_iterable {
-        moves.push(mov);
-    }
-
-    legal_iterable.set_iterator_mask(!EMPTY);
-    for mov in &mut legal_iterable {
-        moves.push(mov);
-    }
-
-    return moves;
-}
@@ -0,0 +1,343 @@
+use chess::{get_rank, BitBoard, Board, ChessMove, MoveGen, Piece, EMPTY};
+
+use crate::moves::see::{see, see_value};
+use crate::tables::history::{History, PieceTo};
+
+/**
+ * More than the legal moves of any position.
+ */
+pub const MAX_MOVES: usize = 256;
+
+/**
+ * Captures that don't lose material are ordered by MVV-LVA with these on top,
+ * the most valuable victim first and the least valuable attacker first among those.
+ */
+fn mvv_lva(board: &Board, chess_move: ChessMove) -> i32 {
+    let victim = board.piece_on(chess_move.get_dest()).map_or(0, see_value);
+    let promotion = chess_move.get_promotion().map_or(0, see_value);
+    let attacker = board
+        .piece_on(chess_move.get_source())
+        .map_or(0, |piece| piece.to_index() as i32);
+    (victim + promotion) * 8 - attacker
+}
+
+/**
+ * Not a capture (en passant included) or a promotion.
+ */
+pub fn is_quiet(board: &Board, chess_move: ChessMove) -> bool {
+    let source = chess_move.get_source();
+    let dest = chess_move.get_dest();
+    let en_passant =
+        board.piece_on(source) == Some(Piece::Pawn) && source.get_file() != dest.get_file();
+    board.piece_on(dest).is_none() && chess_move.get_promotion().is_none() && !en_passant
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Stage {
+    HashMove,
+    GenerateCaptures,
+    GoodCaptures,
+    Killers,
+    Countermove,
+    GenerateQuiets,
+    Quiets,
+    BadCaptures,
+    Done,
+}
+
+/**
+ * What a generated move is waiting for, so every stage only picks its own moves.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    GoodCapture,
+    BadCapture,
+    Quiet,
+    Picked,
+}
+
+#[derive(Clone, Copy)]
+struct Entry {
+    chess_move: ChessMove,
+    score: i32,
+    kind: Kind,
+}
+
+/**
+ * Hands out the moves of a node one by one, best first:
+ * the hash move, captures that don't lose material (by SEE, then MVV-LVA), the killers,
+ * the countermove, the other quiet moves by history and the captures that lose material.
+ *
+ * The moves are only generated when their stage is reached (`MoveGen::set_iterator_mask`
+ * leaves the other moves for later), so a cutoff by the hash move or a capture
+ * saves generating the quiet moves. Everything lives on the stack.
+ */
+pub struct MovePicker {
+    stage: Stage,
+    move_gen: MoveGen,
+    hash_move: Option<ChessMove>,
+    killers: [Option<ChessMove>; 4],
+    killer_index: usize,
+    countermove: Option<ChessMove>,
+    previous: Option<PieceTo>,
+    captures_only: bool,
+    entries: [Entry; MAX_MOVES],
+    len: usize,
+}
+
+impl MovePicker {
+    pub fn new(
+        board: &Board,
+        hash_move: Option<ChessMove>,
+        killers: &[Option<ChessMove>],
+        countermove: Option<ChessMove>,
+        previous: Option<PieceTo>,
+    ) -> Self {
+        let mut stored_killers = [None; 4];
+        for (stored, killer) in stored_killers.iter_mut().zip(killers) {
+            *stored = *killer;
+        }
+        Self {
+            stage: Stage::HashMove,
+            move_gen: MoveGen::new_legal(board),
+            hash_move,
+            killers: stored_killers,
+            killer_index: 0,
+            countermove,
+            previous,
+            captures_only: false,
+            entries: [Entry {
+                chess_move: ChessMove::default(),
+                score: 0,
+                kind: Kind::Picked,
+            }; MAX_MOVES],
+            len: 0,
+        }
+    }
+
+    /**
+     * Only the captures (and promotions) that don't lose material, for the quiescence search.
+     */
+    pub fn captures(board: &Board) -> Self {
+        let mut picker = Self::new(board, None, &[], None, None);
+        picker.stage = Stage::GenerateCaptures;
+        picker.captures_only = true;
+        picker
+    }
+
+    pub fn next_move(&mut self, board: &Board, history: &History) -> Option<ChessMove> {
+        loop {
+            match self.stage {
+                Stage::HashMove => {
+                    self.stage = Stage::GenerateCaptures;
+                    if let Some(hash_move) = self.hash_move {
+                        // the hash move can be from another position with the same key,
+                        // it's only played if it's generated here too.
+                        self.generate(board, BitBoard::from_square(hash_move.get_dest()));
+                        if self.take(hash_move) {
+                            return Some(hash_move);
+                        }
+                    }
+                }
+                Stage::GenerateCaptures => {
+                    let mut targets = *board.color_combined(!board.side_to_move());
+                    if let Some(ep_square) = board.en_passant() {
+                        // chess stores the square of the pawn that can be taken, not the square we land on.
+                        if let Some(dest) = ep_square.forward(board.side_to_move()) {
+                            targets |= BitBoard::from_square(dest);
+                        }
+                    }
+                    let promotion_rank = get_rank(board.side_to_move().to_their_backrank());
+                    self.generate(board, targets | promotion_rank);
+                    self.stage = Stage::GoodCaptures;
+                }
+                Stage::GoodCaptures => {
+                    if let Some(chess_move) = self.pick_best(Kind::GoodCapture) {
+                        return Some(chess_move);
+                    }
+                    self.stage = if self.captures_only {
+                        Stage::Done
+                    } else {
+                        Stage::Killers
+                    };
+                }
+                Stage::Killers => {
+                    if self.killer_index == 0 {
+                        let mut squares = EMPTY;
+                        for killer in self.killers.iter().chain([&self.countermove]).flatten() {
+                            squares |= BitBoard::from_square(killer.get_dest());
+                        }
+                        self.generate(board, squares);
+                    }
+                    if self.killer_index >= self.killers.len() {
+                        self.stage = Stage::Countermove;
+                        continue;
+                    }
+                    let killer = self.killers[self.killer_index];
+                    self.killer_index += 1;
+                    if let Some(killer) = killer.filter(|killer| self.take_quiet(*killer)) {
+                        return Some(killer);
+                    }
+                }
+                Stage::Countermove => {
+                    self.stage = Stage::GenerateQuiets;
+                    if let Some(countermove) = self.countermove {
+                        if self.take_quiet(countermove) {
+                            return Some(countermove);
+                        }
+                    }
+                }
+                Stage::GenerateQuiets => {
+                    self.generate(board, !EMPTY);
+                    for entry in self.entries[..self.len].iter_mut() {
+                        if entry.kind == Kind::Quiet {
+                            entry.score = history.quiet_score(board, entry.chess_move, self.previous);
+                        }
+                    }
+                    self.stage = Stage::Quiets;
+                }
+                Stage::Quiets => {
+                    if let Some(chess_move) = self.pick_best(Kind::Quiet) {
+                        return Some(chess_move);
+                    }
+                    self.stage = Stage::BadCaptures;
+                }
+                Stage::BadCaptures => {
+                    if let Some(chess_move) = self.pick_best(Kind::BadCapture) {
+                        return Some(chess_move);
+                    }
+                    self.stage = Stage::Done;
+                }
+                Stage::Done => return None,
+            }
+        }
+    }
+
+    /**
+     * Adds the legal moves landing on `mask` that weren't generated yet.
+     */
+    fn generate(&mut self, board: &Board, mask: BitBoard) {
+        self.move_gen.set_iterator_mask(mask);
+        for chess_move in &mut self.move_gen {
+            let (score, kind) = if is_quiet(board, chess_move) {
+                (0, Kind::Quiet)
+            } else {
+                let see = see(board, chess_move);
+                if see >= 0 {
+                    (mvv_lva(board, chess_move), Kind::GoodCapture)
+                } else {
+                    (see, Kind::BadCapture)
+                }
+            };
+            self.entries[self.len] = Entry {
+                chess_move,
+                score,
+                kind,
+            };
+            self.len += 1;
+        }
+    }
+
+    /**
+     * Marks `chess_move` as picked if it was generated and not picked yet.
+     */
+    fn take(&mut self, chess_move: ChessMove) -> bool {
+        match self.entries[..self.len]
+            .iter_mut()
+            .find(|entry| entry.chess_move == chess_move && entry.kind != Kind::Picked)
+        {
+            Some(entry) => {
+                entry.kind = Kind::Picked;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /**
+     * Like `take`, but only for quiet moves, killers that became captures are picked as captures.
+     */
+    fn take_quiet(&mut self, chess_move: ChessMove) -> bool {
+        let quiet = self.entries[..self.len]
+            .iter()
+            .any(|entry| entry.chess_move == chess_move && entry.kind == Kind::Quiet);
+        quiet && self.take(chess_move)
+    }
+
+    fn pick_best(&mut self, kind: Kind) -> Option<ChessMove> {
+        let mut best: Option<usize> = None;
+        for (index, entry) in self.entries[..self.len].iter().enumerate() {
+            if entry.kind == kind && best.is_none_or(|best| entry.score > self.entries[best].score)
+            {
+                best = Some(index);
+            }
+        }
+        best.map(|index| {
+            self.entries[index].kind = Kind::Picked;
+            self.entries[index].chess_move
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MovePicker;
+    use crate::tables::history::History;
+    use chess::{Board, ChessMove, MoveGen};
+    use std::collections::HashSet;
+    use std::str::FromStr;
+
+    const POSITIONS: [&str; 4] = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1",
+    ];
+
+    fn picked(mut picker: MovePicker, board: &Board) -> Vec<ChessMove> {
+        let history = History::default();
+        let mut moves = Vec::new();
+        while let Some(chess_move) = picker.next_move(board, &history) {
+            moves.push(chess_move);
+        }
+        moves
+    }
+
+    #[test]
+    fn picks_every_legal_move_once() {
+        for fen in POSITIONS {
+            let board = Board::from_str(fen).unwrap();
+            let legal: HashSet<ChessMove> = MoveGen::new_legal(&board).collect();
+            // a killer and a hash move that may or may not be legal here.
+            let e2e4 = ChessMove::from_str("e2e4").unwrap();
+            let a1b1 = ChessMove::from_str("a1b1").unwrap();
+            let moves = picked(
+                MovePicker::new(&board, Some(e2e4), &[Some(a1b1), Some(e2e4)], None, None),
+                &board,
+            );
+
+            let unique: HashSet<ChessMove> = moves.iter().copied().collect();
+            assert_eq!(unique.len(), moves.len(), "{}", fen);
+            assert_eq!(unique, legal, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn picks_the_hash_move_first_and_bad_captures_last() {
+        // the queen can take a defended pawn on d5 (bad) or an undefended knight on a4 (good).
+        let board = Board::from_str("4k3/8/2p5/3p4/n7/8/8/3QK3 w - - 0 1").unwrap();
+        let hash_move = ChessMove::from_str("e1f2").unwrap();
+        let moves = picked(MovePicker::new(&board, Some(hash_move), &[], None, None), &board);
+
+        assert_eq!(moves[0], hash_move);
+        assert_eq!(moves[1], ChessMove::from_str("d1a4").unwrap());
+        assert_eq!(*moves.last().unwrap(), ChessMove::from_str("d1d5").unwrap());
+    }
+
+    #[test]
+    fn quiescence_gets_only_good_captures() {
+        let board = Board::from_str("4k3/8/2p5/3p4/n7/8/8/3QK3 w - - 0 1").unwrap();
+        let moves = picked(MovePicker::captures(&board), &board);
+        assert_eq!(moves, vec![ChessMove::from_str("d1a4").unwrap()]);
+    }
+}
@@ -0,0 +1,192 @@
+use std::cmp;
+
+use chess::{
+    get_bishop_moves, get_king_moves, get_knight_moves, get_pawn_attacks, get_rook_moves, BitBoard,
+    Board, ChessMove, Color, Piece, Square, EMPTY,
+};
+
+/**
+ * Piece values of the exchange, the king is worth more than anything it could win.
+ */
+pub fn see_value(piece: Piece) -> i32 {
+    match piece {
+        Piece::Pawn => 100,
+        Piece::Knight | Piece::Bishop => 300,
+        Piece::Rook => 500,
+        Piece::Queen => 900,
+        Piece::King => 20000,
+    }
+}
+
+/**
+ * Every piece (of both colors) in `occupied` that attacks `square`.
+ * Sliders are looked up through `occupied`, so removing a piece from it
+ * reveals the x-ray attackers behind it.
+ */
+fn attackers_to(board: &Board, square: Square, occupied: BitBoard) -> BitBoard {
+    let pawns = board.pieces(Piece::Pawn);
+    let white_pawns = pawns & board.color_combined(Color::White);
+    let black_pawns = pawns & board.color_combined(Color::Black);
+    let diagonal = board.pieces(Piece::Bishop) | board.pieces(Piece::Queen);
+    let straight = board.pieces(Piece::Rook) | board.pieces(Piece::Queen);
+
+    // a pawn attacks `square` if a pawn of the other color on `square` would attack it back.
+    let attackers = get_pawn_attacks(square, Color::White, black_pawns)
+        | get_pawn_attacks(square, Color::Black, white_pawns)
+        | (get_knight_moves(square) & board.pieces(Piece::Knight))
+        | (get_king_moves(square) & board.pieces(Piece::King))
+        | (get_bishop_moves(square, occupied) & diagonal)
+        | (get_rook_moves(square, occupied) & straight);
+    attackers & occupied
+}
+
+fn least_valuable_attacker(board: &Board, attackers: BitBoard) -> Option<(Piece, Square)> {
+    [
+        Piece::Pawn,
+        Piece::Knight,
+        Piece::Bishop,
+        Piece::Rook,
+        Piece::Queen,
+        Piece::King,
+    ]
+    .into_iter()
+    .find_map(|piece| {
+        let pieces = attackers & board.pieces(piece);
+        (pieces != EMPTY).then(|| (piece, pieces.to_square()))
+    })
+}
+
+/**
+ * Static exchange evaluation: what the side to move wins (or loses, if negative)
+ * in centipawns on the destination square of `chess_move`, if both sides keep
+ * recapturing there with their least valuable piece and stop whenever recapturing doesn't pay.
+ *
+ * Only the destination square is looked at, pins and checks elsewhere are ignored.
+ */
+pub fn see(board: &Board, chess_move: ChessMove) -> i32 {
+    let source = chess_move.get_source();
+    let dest = chess_move.get_dest();
+    let Some(piece) = board.piece_on(source) else {
+        return 0;
+    };
+
+    let mut occupied = *board.combined() ^ BitBoard::from_square(source);
+    let mut gain = [0; 32];
+
+    gain[0] = match board.piece_on(dest) {
+        Some(captured) => see_value(captured),
+        None if piece == Piece::Pawn && source.get_file() != dest.get_file() => {
+            // en passant, the captured pawn isn't on the destination square.
+            occupied ^= BitBoard::from_square(dest.ubackward(board.side_to_move()));
+            see_value(Piece::Pawn)
+        }
+        None => 0,
+    };
+    // the piece standing on the square, which the next capture would win.
+    let mut on_square = match chess_move.get_promotion() {
+        Some(promotion) => {
+            gain[0] += see_value(promotion) - see_value(Piece::Pawn);
+            see_value(promotion)
+        }
+        None => see_value(piece),
+    };
+
+    let mut side = !board.side_to_move();
+    let mut depth = 0;
+    loop {
+        let attackers = attackers_to(board, dest, occupied);
+        let Some((attacker, square)) =
+            least_valuable_attacker(board, attackers & board.color_combined(side))
+        else {
+            break;
+        };
+        // the king can't take a defended piece.
+        if attacker == Piece::King && attackers & board.color_combined(!side) != EMPTY {
+            break;
+        }
+
+        depth += 1;
+        gain[depth] = on_square - gain[depth - 1];
+        on_square = see_value(attacker);
+        occupied ^= BitBoard::from_square(square);
+        side = !side;
+
+        if depth == gain.len() - 1 {
+            break;
+        }
+    }
+
+    // every side can stop capturing instead, going back from the last capture.
+    while depth > 0 {
+        gain[depth - 1] = -cmp::max(-gain[depth - 1], gain[depth]);
+        depth -= 1;
+    }
+    gain[0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::see;
+    use chess::{Board, ChessMove};
+    use std::str::FromStr;
+
+    fn see_of(fen: &str, chess_move: &str) -> i32 {
+        let board = Board::from_str(fen).unwrap();
+        let chess_move = ChessMove::from_str(chess_move).unwrap();
+        assert!(board.legal(chess_move), "{} isn't legal in {}", chess_move, fen);
+        see(&board, chess_move)
+    }
+
+    #[test]
+    fn wins_an_undefended_pawn() {
+        assert_eq!(see_of("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5"), 100);
+    }
+
+    #[test]
+    fn loses_a_rook_for_a_defended_pawn() {
+        assert_eq!(see_of("4k3/3p4/4p3/8/8/8/8/4R1K1 w - - 0 1", "e1e6"), -400);
+    }
+
+    #[test]
+    fn plays_out_a_long_exchange() {
+        // both sides have batteries pointing at e5, after NxP NxN going on only loses more.
+        assert_eq!(
+            see_of("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1", "d3e5"),
+            -200
+        );
+    }
+
+    #[test]
+    fn counts_x_ray_attackers() {
+        // the rook on d1 recaptures through the rook on d2.
+        assert_eq!(see_of("3rk3/8/8/3p4/8/8/3R4/3R2K1 w - - 0 1", "d2d5"), 100);
+        // without it, the rook is lost for a pawn.
+        assert_eq!(see_of("3rk3/8/8/3p4/8/8/3R4/6K1 w - - 0 1", "d2d5"), -400);
+    }
+
+    #[test]
+    fn captures_en_passant() {
+        assert_eq!(see_of("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"), 100);
+    }
+
+    #[test]
+    fn counts_promotions() {
+        assert_eq!(see_of("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7b8q"), 800);
+        // the new queen is taken right away.
+        assert_eq!(see_of("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7a8q"), -100);
+    }
+
+    #[test]
+    fn king_only_takes_undefended_pieces() {
+        // RxP NxR KxN.
+        assert_eq!(see_of("4k3/8/8/8/2n5/8/3p4/3RK3 w - - 0 1", "d1d2"), -100);
+        // the bishop defends d2, so the king can't take back.
+        assert_eq!(see_of("4k3/8/8/b7/2n5/8/3p4/3RK3 w - - 0 1", "d1d2"), -400);
+    }
+
+    #[test]
+    fn quiet_moves_only_lose_if_the_piece_can_be_taken() {
+        assert_eq!(see_of("4k3/8/8/3p4/8/8/8/2N1K3 w - - 0 1", "c1b3"), 0);
+        assert_eq!(see_of("4k3/8/8/3p4/8/8/1N6/4K3 w - - 0 1", "b2c4"), -300);
+    }
+}
@@ -2,10 +2,6 @@ use std::io::stdin;
 
 use chess::{Board, ChessMove};
 
-/**
- * Returns the pieces but colored.
- */
-
 /**
  * h8Q = promotions
  * Qh6 = more specific movement (Queen goes to h6)
@@ -18,5 +14,5 @@ pub fn get_user_move(board: &Board) -> Result<ChessMove, chess::Error> {
 
     let input = input.trim();
 
-    ChessMove::from_san(&board, input)
+    ChessMove::from_san(board, input)
 }
@@ -0,0 +1,189 @@
+use chess::{Board, ChessMove, Color, Piece, Square};
+
+/**
+ * Scores never grow past this, every update pulls them back towards 0 the closer they get ("gravity").
+ */
+pub const MAX_HISTORY: i32 = 16384;
+
+/**
+ * A moved piece (with its color) and the square it moved to, used to index
+ * the tables that remember moves by what they did instead of where they came from.
+ */
+pub type PieceTo = usize;
+
+const PIECE_TO_COUNT: usize = 2 * 6 * 64;
+
+pub fn piece_to(piece: Piece, color: Color, to: Square) -> PieceTo {
+    (color.to_index() * 6 + piece.to_index()) * 64 + to.to_index()
+}
+
+/**
+ * The piece-to of the move that led to `board`, the moved piece is on its destination now.
+ */
+pub fn previous_piece_to(board: &Board, previous_move: ChessMove) -> Option<PieceTo> {
+    let to = previous_move.get_dest();
+    board
+        .piece_on(to)
+        .map(|piece| piece_to(piece, !board.side_to_move(), to))
+}
+
+/**
+ * What the search learned about quiet moves, used to order them.
+ *
+ * - butterfly history: how often a move (by side, from and to square) caused a beta cutoff.
+ * - countermoves: the quiet move that last refuted a move.
+ * - continuation history: like the butterfly history, but for a move played right after another one.
+ */
+pub struct History {
+    butterfly: Box<[[[i32; 64]; 64]; 2]>,
+    countermoves: Box<[Option<ChessMove>; PIECE_TO_COUNT]>,
+    continuation: Vec<i32>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            butterfly: Box::new([[[0; 64]; 64]; 2]),
+            countermoves: Box::new([None; PIECE_TO_COUNT]),
+            continuation: vec![0; PIECE_TO_COUNT * PIECE_TO_COUNT],
+        }
+    }
+}
+
+impl History {
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /**
+     * Halves every score, so what was learned in earlier searches counts less than the new.
+     */
+    pub fn decay(&mut self) {
+        for score in self.butterfly.iter_mut().flatten().flatten() {
+            *score /= 2;
+        }
+        for score in self.continuation.iter_mut() {
+            *score /= 2;
+        }
+    }
+
+    /**
+     * How good the quiet move `chess_move` has been, higher is better.
+     */
+    pub fn quiet_score(
+        &self,
+        board: &Board,
+        chess_move: ChessMove,
+        previous: Option<PieceTo>,
+    ) -> i32 {
+        let color = board.side_to_move();
+        let mut score = self.butterfly[color.to_index()][chess_move.get_source().to_index()]
+            [chess_move.get_dest().to_index()];
+
+        if let (Some(previous), Some(piece)) = (previous, board.piece_on(chess_move.get_source())) {
+            score += self.continuation
+                [previous * PIECE_TO_COUNT + piece_to(piece, color, chess_move.get_dest())];
+        }
+        score
+    }
+
+    pub fn countermove(&self, previous: Option<PieceTo>) -> Option<ChessMove> {
+        previous.and_then(|previous| self.countermoves[previous])
+    }
+
+    /**
+     * Rewards the quiet move that caused a beta cutoff, and punishes the quiet moves
+     * searched before it that didn't.
+     */
+    pub fn update(
+        &mut self,
+        board: &Board,
+        previous: Option<PieceTo>,
+        best_move: ChessMove,
+        quiets_searched: &[ChessMove],
+        depth: u16,
+    ) {
+        let bonus = history_bonus(depth);
+        for chess_move in quiets_searched {
+            let bonus = if *chess_move == best_move { bonus } else { -bonus };
+            self.update_move(board, previous, *chess_move, bonus);
+        }
+        if let Some(previous) = previous {
+            self.countermoves[previous] = Some(best_move);
+        }
+    }
+
+    fn update_move(
+        &mut self,
+        board: &Board,
+        previous: Option<PieceTo>,
+        chess_move: ChessMove,
+        bonus: i32,
+    ) {
+        let color = board.side_to_move();
+        let score = &mut self.butterfly[color.to_index()][chess_move.get_source().to_index()]
+            [chess_move.get_dest().to_index()];
+        apply_gravity(score, bonus);
+
+        if let (Some(previous), Some(piece)) = (previous, board.piece_on(chess_move.get_source())) {
+            let index = previous * PIECE_TO_COUNT + piece_to(piece, color, chess_move.get_dest());
+            apply_gravity(&mut self.continuation[index], bonus);
+        }
+    }
+}
+
+/**
+ * Deeper cutoffs say more about a move, capped so a few deep ones don't saturate the table.
+ */
+fn history_bonus(depth: u16) -> i32 {
+    (depth as i32 * depth as i32 * 16).min(MAX_HISTORY / 8)
+}
+
+fn apply_gravity(score: &mut i32, bonus: i32) {
+    *score += bonus - *score * bonus.abs() / MAX_HISTORY;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{previous_piece_to, History, MAX_HISTORY};
+    use chess::{Board, ChessMove};
+    use std::str::FromStr;
+
+    #[test]
+    fn rewards_the_cutoff_move_and_punishes_the_others() {
+        let mut history = History::default();
+        let board = Board::default();
+        let good = ChessMove::from_str("g1f3").unwrap();
+        let bad = ChessMove::from_str("a2a3").unwrap();
+
+        history.update(&board, None, good, &[bad, good], 5);
+        assert!(history.quiet_score(&board, good, None) > 0);
+        assert!(history.quiet_score(&board, bad, None) < 0);
+    }
+
+    #[test]
+    fn scores_stay_below_the_maximum() {
+        let mut history = History::default();
+        let board = Board::default();
+        let good = ChessMove::from_str("g1f3").unwrap();
+
+        for _ in 0..1000 {
+            history.update(&board, None, good, &[good], 20);
+        }
+        let score = history.quiet_score(&board, good, None);
+        assert!(score > MAX_HISTORY / 2 && score <= MAX_HISTORY, "{}", score);
+    }
+
+    #[test]
+    fn remembers_countermoves() {
+        let mut history = History::default();
+        let e4 = ChessMove::from_str("e2e4").unwrap();
+        let board = Board::default().make_move_new(e4);
+        let previous = previous_piece_to(&board, e4);
+        let reply = ChessMove::from_str("c7c5").unwrap();
+
+        history.update(&board, previous, reply, &[reply], 3);
+        assert_eq!(history.countermove(previous), Some(reply));
+        assert_eq!(history.countermove(None), None);
+    }
+}
@@ -1,2 +1,4 @@
+pub mod history;
 pub mod piece_sq_tables;
+pub mod transposition;
 pub mod zobrist;
@@ -110,15 +110,17 @@ pub fn create_pesto_piece_sqaure() -> (ColoredTables, ColoredTables) {
     let mut black_mg_table: Table = [[0; 64]; 6];
     let mut black_eg_table: Table = [[0; 64]; 6];
 
-    for piece in Piece::Pawn.to_index()..Piece::King.to_index() {
+    // the tables above start at a8 (from white's point of view), chess' squares start at a1.
+    // flipping the rank gives white's table, black's table is already the right way around.
+    for piece in Piece::Pawn.to_index()..=Piece::King.to_index() {
         for sq in 0..64 {
-            let rev_sq = 63 - sq;
+            let flipped_sq = sq ^ 56;
 
-            white_mg_table[piece][sq] = mg_value[piece] + mg_pesto_table[piece][sq];
-            white_eg_table[piece][sq] = eg_value[piece] + eg_pesto_table[piece][sq];
+            white_mg_table[piece][sq] = mg_value[piece] + mg_pesto_table[piece][flipped_sq];
+            white_eg_table[piece][sq] = eg_value[piece] + eg_pesto_table[piece][flipped_sq];
 
-            black_mg_table[piece][rev_sq] = mg_value[piece] + mg_pesto_table[piece][rev_sq];
-            black_eg_table[piece][rev_sq] = eg_value[piece] + eg_pesto_table[piece][rev_sq];
+            black_mg_table[piece][sq] = mg_value[piece] + mg_pesto_table[piece][sq];
+            black_eg_table[piece][sq] = eg_value[piece] + eg_pesto_table[piece][sq];
         }
     }
 
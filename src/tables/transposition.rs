@@ -0,0 +1,367 @@
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+use chess::{ChessMove, Piece, ALL_SQUARES};
+
+/**
+ * How many entries share one index of the table.
+ */
+const BUCKET_SIZE: usize = 4;
+
+/**
+ * What the stored score says about the real score of the position,
+ * relative to the side to move.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bound {
+    /**
+     * The score is exact, every move was searched inside the window.
+     */
+    Exact,
+    /**
+     * The search failed high (beta cutoff), the real score is at least this.
+     */
+    Lower,
+    /**
+     * The search failed low (no move raised alpha), the real score is at most this.
+     */
+    Upper,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TTEntry {
+    pub best_move: Option<ChessMove>,
+    pub score: i32,
+    pub depth: u16,
+    pub bound: Bound,
+    age: u8,
+}
+
+const PROMOTIONS: [Piece; 4] = [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen];
+
+impl TTEntry {
+    /**
+     * Packs the entry into one word: the move in bits 0-15, the score in 16-39,
+     * the depth in 40-47, the bound in 48-49 and the age in 56-63.
+     * The bound is never 0, so an empty slot is the only 0.
+     */
+    fn pack(&self) -> u64 {
+        let chess_move = self.best_move.map_or(0, |chess_move| {
+            let promotion = chess_move
+                .get_promotion()
+                .and_then(|piece| PROMOTIONS.iter().position(|&promotion| promotion == piece))
+                .map_or(0, |index| index as u64 + 1);
+            1 << 15
+                | promotion << 12
+                | (chess_move.get_source().to_index() as u64) << 6
+                | chess_move.get_dest().to_index() as u64
+        });
+        let bound = match self.bound {
+            Bound::Exact => 1,
+            Bound::Lower => 2,
+            Bound::Upper => 3,
+        };
+        chess_move
+            | ((self.score as u32 as u64) & 0xFFFFFF) << 16
+            | (self.depth.min(u8::MAX as u16) as u64) << 40
+            | bound << 48
+            | (self.age as u64) << 56
+    }
+
+    fn unpack(data: u64) -> Option<TTEntry> {
+        let bound = match (data >> 48) & 0b11 {
+            1 => Bound::Exact,
+            2 => Bound::Lower,
+            3 => Bound::Upper,
+            _ => return None,
+        };
+        let best_move = (data & 1 << 15 != 0).then(|| {
+            let promotion = ((data >> 12) & 0b111) as usize;
+            ChessMove::new(
+                ALL_SQUARES[((data >> 6) & 63) as usize],
+                ALL_SQUARES[(data & 63) as usize],
+                promotion.checked_sub(1).map(|index| PROMOTIONS[index]),
+            )
+        });
+        Some(TTEntry {
+            best_move,
+            // the score's sign comes back by shifting it into the top of an i32 and back.
+            score: (((data >> 16) as u32) << 8) as i32 >> 8,
+            depth: ((data >> 40) & 0xFF) as u16,
+            bound,
+            age: (data >> 56) as u8,
+        })
+    }
+}
+
+/**
+ * An entry is written as two words, its data and the zobrist key XOR the data.
+ * Threads write to the table without locking, if two writes to a slot mix,
+ * the key doesn't match the data anymore and the slot reads as empty.
+ */
+#[derive(Default)]
+struct Slot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+impl Slot {
+    fn load(&self, key: u64) -> Option<TTEntry> {
+        let data = self.data.load(Ordering::Relaxed);
+        let stored_key = self.key.load(Ordering::Relaxed);
+        if stored_key ^ data == key {
+            TTEntry::unpack(data)
+        } else {
+            None
+        }
+    }
+
+    /**
+     * The entry in the slot, whatever position it's from.
+     */
+    fn load_any(&self) -> Option<TTEntry> {
+        TTEntry::unpack(self.data.load(Ordering::Relaxed))
+    }
+
+    fn save(&self, key: u64, entry: &TTEntry) {
+        let data = entry.pack();
+        self.key.store(key ^ data, Ordering::Relaxed);
+        self.data.store(data, Ordering::Relaxed);
+    }
+
+    fn clear(&self) {
+        self.key.store(0, Ordering::Relaxed);
+        self.data.store(0, Ordering::Relaxed);
+    }
+}
+
+/**
+ * Four slots of 16 bytes, one cache line.
+ */
+type Bucket = [Slot; BUCKET_SIZE];
+
+/**
+ * A fixed-size transposition table, allocated once and shared by every search thread.
+ *
+ * Each zobrist key maps to a bucket of a few entries. When a bucket is full,
+ * the entry with the lowest depth is replaced, with entries from older searches
+ * counting as shallower than they are.
+ */
+pub struct TranspositionTable {
+    buckets: Vec<Bucket>,
+    age: AtomicU8,
+}
+
+impl TranspositionTable {
+    pub fn new(byte_size: usize) -> Self {
+        let bucket_count = (byte_size / std::mem::size_of::<Bucket>()).max(1);
+        let mut buckets = Vec::with_capacity(bucket_count);
+        buckets.resize_with(bucket_count, Bucket::default);
+        TranspositionTable {
+            buckets,
+            age: AtomicU8::new(0),
+        }
+    }
+
+    fn index(&self, key: u64) -> usize {
+        (key % self.buckets.len() as u64) as usize
+    }
+
+    fn age(&self) -> u8 {
+        self.age.load(Ordering::Relaxed)
+    }
+
+    /**
+     * Has to be called before every search (once, not by every thread), so entries left
+     * by older searches get replaced first.
+     */
+    pub fn new_search(&self) {
+        self.age.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        for slot in self.buckets.iter().flatten() {
+            slot.clear();
+        }
+        self.age.store(0, Ordering::Relaxed);
+    }
+
+    pub fn probe(&self, key: u64) -> Option<TTEntry> {
+        self.buckets[self.index(key)]
+            .iter()
+            .find_map(|slot| slot.load(key))
+    }
+
+    pub fn store(
+        &self,
+        key: u64,
+        depth: u16,
+        score: i32,
+        bound: Bound,
+        best_move: Option<ChessMove>,
+    ) {
+        let age = self.age();
+        let bucket = &self.buckets[self.index(key)];
+
+        let (slot, old) = match bucket
+            .iter()
+            .enumerate()
+            .find_map(|(slot, entry)| entry.load(key).map(|entry| (slot, entry)))
+        {
+            Some((slot, old)) => {
+                // a shallower search of the same position only replaces
+                // the old one if it is exact or the old one is stale.
+                if bound != Bound::Exact && old.age == age && depth + 2 < old.depth {
+                    return;
+                }
+                (slot, Some(old))
+            }
+            _ => match bucket.iter().position(|slot| slot.load_any().is_none()) {
+                Some(slot) => (slot, None),
+                None => (
+                    (0..BUCKET_SIZE)
+                        .min_by_key(|&slot| {
+                            bucket[slot].load_any().map_or(i32::MIN, |entry| {
+                                entry.depth as i32 - 4 * age.wrapping_sub(entry.age) as i32
+                            })
+                        })
+                        .unwrap(),
+                    None,
+                ),
+            },
+        };
+
+        // keep the old move if the new search didn't find one (e.g. it failed low).
+        let best_move = best_move.or_else(|| old.and_then(|old| old.best_move));
+
+        bucket[slot].save(
+            key,
+            &TTEntry {
+                best_move,
+                score,
+                depth,
+                bound,
+                age,
+            },
+        );
+    }
+
+    /**
+     * How full the table is in permill, sampled from the first thousand buckets.
+     */
+    pub fn hashfull(&self) -> u16 {
+        let age = self.age();
+        let sample = self.buckets.len().min(1000);
+        let used = self.buckets[..sample]
+            .iter()
+            .flatten()
+            .filter_map(Slot::load_any)
+            .filter(|entry| entry.age == age)
+            .count();
+        (used * 1000 / (sample * BUCKET_SIZE)) as u16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Bound, TTEntry, TranspositionTable};
+    use crate::types::score;
+    use chess::ChessMove;
+    use std::str::FromStr;
+
+    #[test]
+    fn entries_survive_packing() {
+        for (chess_move, score) in [
+            (Some("e2e4"), 35),
+            (Some("a7a8n"), -score::INFINITY),
+            (Some("h2h1q"), score::mate_in(7)),
+            (None, score::mated_in(12)),
+        ] {
+            let entry = TTEntry {
+                best_move: chess_move.map(|chess_move| ChessMove::from_str(chess_move).unwrap()),
+                score,
+                depth: 17,
+                bound: Bound::Lower,
+                age: 200,
+            };
+            assert_eq!(TTEntry::unpack(entry.pack()), Some(entry));
+        }
+    }
+
+    #[test]
+    fn a_mixed_up_slot_reads_as_empty() {
+        let table = TranspositionTable::new(1024);
+        let key = 0x1234_5678_9abc_def0;
+        table.store(key, 5, 120, Bound::Exact, None);
+        assert_eq!(table.probe(key).map(|entry| entry.score), Some(120));
+        assert_eq!(table.probe(key ^ 1), None);
+
+        // the data of another write lands in the slot, but not its key.
+        let slot = table.buckets[table.index(key)]
+            .iter()
+            .find(|slot| slot.load(key).is_some())
+            .unwrap();
+        slot.data.fetch_xor(1 << 20, std::sync::atomic::Ordering::Relaxed);
+        assert_eq!(table.probe(key), None);
+    }
+
+    #[test]
+    fn a_shallower_search_only_replaces_exact_or_stale_entries() {
+        let table = TranspositionTable::new(1024);
+        let key = 42;
+        table.store(key, 10, 120, Bound::Exact, None);
+
+        table.store(key, 5, 300, Bound::Lower, None);
+        assert_eq!(table.probe(key).map(|entry| entry.depth), Some(10));
+        table.store(key, 5, -80, Bound::Upper, None);
+        assert_eq!(table.probe(key).map(|entry| entry.depth), Some(10));
+
+        table.store(key, 5, 90, Bound::Exact, None);
+        assert_eq!(table.probe(key).map(|entry| (entry.depth, entry.score)), Some((5, 90)));
+
+        table.store(key, 10, 120, Bound::Exact, None);
+        table.new_search();
+        table.store(key, 5, 300, Bound::Lower, None);
+        assert_eq!(table.probe(key).map(|entry| entry.bound), Some(Bound::Lower));
+    }
+
+    #[test]
+    fn stale_entries_are_replaced_first() {
+        // a single bucket, every key lands in it.
+        let table = TranspositionTable::new(64);
+        assert_eq!(table.buckets.len(), 1);
+
+        table.store(1, 6, 0, Bound::Exact, None);
+        table.new_search();
+        for key in 2..=4 {
+            table.store(key, 3, 0, Bound::Exact, None);
+        }
+        // the full bucket gives up the stale entry, deeper as it is.
+        table.store(5, 3, 0, Bound::Exact, None);
+        assert_eq!(table.probe(1), None);
+        assert!((2..=5).all(|key| table.probe(key).is_some()));
+
+        // among entries of this search, the shallowest goes.
+        table.store(6, 5, 0, Bound::Exact, None);
+        table.store(7, 4, 0, Bound::Exact, None);
+        assert_eq!(table.probe(6).map(|entry| entry.depth), Some(5));
+        assert_eq!(table.probe(7).map(|entry| entry.depth), Some(4));
+        assert_eq!((2..=5).filter(|&key| table.probe(key).is_some()).count(), 2);
+    }
+
+    #[test]
+    fn a_fail_low_keeps_the_old_best_move() {
+        let table = TranspositionTable::new(1024);
+        let key = 42;
+        let e2e4 = ChessMove::from_str("e2e4").unwrap();
+        table.store(key, 5, 40, Bound::Lower, Some(e2e4));
+
+        // a fail low doesn't know which move is best.
+        table.store(key, 6, -20, Bound::Upper, None);
+        let entry = table.probe(key).unwrap();
+        assert_eq!((entry.depth, entry.bound, entry.best_move), (6, Bound::Upper, Some(e2e4)));
+
+        let d2d4 = ChessMove::from_str("d2d4").unwrap();
+        table.store(key, 7, 30, Bound::Exact, Some(d2d4));
+        assert_eq!(table.probe(key).and_then(|entry| entry.best_move), Some(d2d4));
+    }
+}
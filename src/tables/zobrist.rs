@@ -1,124 +1,280 @@
-use chess::{Board, ChessMove, Color, Piece, Square};
-use nohash_hasher::BuildNoHashHasher;
-use rand::Rng;
-use std::collections::VecDeque;
-use std::{collections::HashMap, fmt::Debug};
-
-#[derive(Debug, Clone)]
-pub struct NodeInfo {
-    pub eval: i32,
-    pub best_move: Option<ChessMove>,
-    pub depth: u16,
-}
+use chess::{Board, ChessMove, Color, File, Piece, Square, ALL_COLORS, ALL_SQUARES};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/**
+ * The keys are the same every run, so a search (and a game with a "Seed") can be replayed exactly.
+ */
+const ZOBRIST_SEED: u64 = 0x416C_6963_6521;
 
-struct ZobristValues {
+/**
+ * The random keys every part of a position is hashed with.
+ *
+ * A key of a position is the XOR of the keys of its pieces, the castling rights of both sides,
+ * the en passant file (if any) and the side to move (if black).
+ */
+#[derive(Clone)]
+pub struct ZobristValues {
     white_pieces: [[u64; 6]; 64],
     black_pieces: [[u64; 6]; 64],
-    white_turn: u64,
+    castle_rights: [[u64; 4]; 2],
+    en_passant_files: [u64; 8],
     black_turn: u64,
 }
 
-pub struct ZobristHashMap<V> {
-    map: HashMap<u64, V, BuildNoHashHasher<u64>>,
-    zobrist_table: ZobristValues,
-    keys: VecDeque<u64>,
-    capacity: usize,
+impl Default for ZobristValues {
+    fn default() -> Self {
+        init_zobrist()
+    }
 }
 
-fn capacity_to_bytes<K, V>() -> usize {
-    std::mem::size_of::<K>() + std::mem::size_of::<V>()
-}
-fn bytes_to_capacity<K, V>(total_bytes: usize) -> usize {
-    total_bytes / (std::mem::size_of::<K>() + std::mem::size_of::<V>())
-}
+impl ZobristValues {
+    /**
+     * Hashes the position from scratch.
+     */
+    pub fn hash(&self, board: &Board) -> u64 {
+        let mut final_hash = 0;
 
-impl<V> ZobristHashMap<V>
-where
-    V: Debug,
-{
-    pub fn new(byte_size: usize) -> Self {
-        let capacity = bytes_to_capacity::<u64, V>(byte_size);
-        ZobristHashMap {
-            map: HashMap::with_hasher(BuildNoHashHasher::default()),
-            zobrist_table: init_zobrist(),
-            keys: VecDeque::with_capacity(capacity),
-            capacity,
+        for sq in ALL_SQUARES {
+            if let (Some(piece), Some(color)) = (board.piece_on(sq), board.color_on(sq)) {
+                final_hash ^= self.piece(piece, color, sq);
+            }
         }
+
+        final_hash ^ self.state(board)
     }
-    pub fn insert(&mut self, key: &Board, value: V) -> Option<V> {
-        let hashd = hash(key, &self.zobrist_table);
-        self.keys.push_back(hashd);
-        let result = self.map.insert(hashd, value);
-        self.remove_oldest_if_full();
-        result
-    }
-    pub fn contains(&self, key: &Board) -> bool {
-        let hashd = hash(key, &self.zobrist_table);
-        self.map.contains_key(&hashd)
-    }
-    pub fn get(&self, key: &Board) -> Option<&V> {
-        let hashd = hash(key, &self.zobrist_table);
-        self.map.get(&hashd)
+
+    /**
+     * Updates the key of `board` to the key of `new_board`, the position after `chess_move`.
+     * Only the squares the move touches are hashed again.
+     */
+    pub fn update_hash(
+        &self,
+        hash: u64,
+        board: &Board,
+        chess_move: ChessMove,
+        new_board: &Board,
+    ) -> u64 {
+        let color = board.side_to_move();
+        let source = chess_move.get_source();
+        let dest = chess_move.get_dest();
+        let piece = match board.piece_on(source) {
+            Some(piece) => piece,
+            None => return self.hash(new_board),
+        };
+
+        let mut final_hash = hash ^ self.state(board) ^ self.state(new_board);
+
+        final_hash ^= self.piece(piece, color, source);
+        let placed_piece = chess_move.get_promotion().unwrap_or(piece);
+        final_hash ^= self.piece(placed_piece, color, dest);
+
+        if let Some(captured) = board.piece_on(dest) {
+            final_hash ^= self.piece(captured, !color, dest);
+        } else if piece == Piece::Pawn && source.get_file() != dest.get_file() {
+            // en passant, the captured pawn is behind the destination square.
+            let captured_square = dest.ubackward(color);
+            final_hash ^= self.piece(Piece::Pawn, !color, captured_square);
+        }
+
+        if piece == Piece::King {
+            let source_file = source.get_file().to_index();
+            let dest_file = dest.get_file().to_index();
+            // castling is the only king move going over two files, the rook jumps over the king.
+            if source_file.abs_diff(dest_file) == 2 {
+                let (rook_from, rook_to) = if dest_file > source_file {
+                    (File::H, File::F)
+                } else {
+                    (File::A, File::D)
+                };
+                let rank = source.get_rank();
+                final_hash ^= self.piece(Piece::Rook, color, Square::make_square(rank, rook_from));
+                final_hash ^= self.piece(Piece::Rook, color, Square::make_square(rank, rook_to));
+            }
+        }
+
+        final_hash
     }
-    pub fn len(&self) -> usize {
-        self.map.len()
+
+    /**
+     * Updates the key of `board` to the key of `new_board`, the same position
+     * with the turn passed to the other side.
+     */
+    pub fn update_null_move(&self, hash: u64, board: &Board, new_board: &Board) -> u64 {
+        hash ^ self.state(board) ^ self.state(new_board)
     }
-    pub fn print(&self) {
-        println!("{:#?}", self.map);
+
+    fn piece(&self, piece: Piece, color: Color, sq: Square) -> u64 {
+        match color {
+            Color::White => self.white_pieces[sq.to_index()][piece.to_index()],
+            Color::Black => self.black_pieces[sq.to_index()][piece.to_index()],
+        }
     }
-    pub fn remove_oldest_if_full(&mut self) {
-        if self.keys.len() > self.capacity {
-            if let Some(key) = self.keys.pop_front() {
-                self.map.remove(&key);
-            }
+
+    /**
+     * Everything in the key that isn't a piece: castling rights, en passant and side to move.
+     */
+    fn state(&self, board: &Board) -> u64 {
+        let mut final_hash = 0;
+
+        for color in ALL_COLORS {
+            final_hash ^=
+                self.castle_rights[color.to_index()][board.castle_rights(color).to_index()];
         }
+        if let Some(ep_square) = board.en_passant() {
+            final_hash ^= self.en_passant_files[ep_square.get_file().to_index()];
+        }
+        if board.side_to_move() == Color::Black {
+            final_hash ^= self.black_turn;
+        }
+
+        final_hash
     }
 }
 
 fn init_zobrist() -> ZobristValues {
-    let mut rng = rand::thread_rng();
+    let mut rng = StdRng::seed_from_u64(ZOBRIST_SEED);
     let mut black_zobrist_table: [[u64; 6]; 64] = [[0; 6]; 64];
     let mut white_zobrist_table: [[u64; 6]; 64] = [[0; 6]; 64];
     for sq in 0..64 {
         // 0 to 63 (inclusive)
-        for piece_index in Piece::Pawn.to_index()..Piece::King.to_index() {
+        for piece_index in Piece::Pawn.to_index()..=Piece::King.to_index() {
             white_zobrist_table[sq][piece_index] = rng.gen_range(0..u64::MAX);
             black_zobrist_table[sq][piece_index] = rng.gen_range(0..u64::MAX);
         }
     }
 
+    let mut castle_rights = [[0; 4]; 2];
+    for rights in castle_rights.iter_mut() {
+        for key in rights.iter_mut() {
+            *key = rng.gen_range(0..u64::MAX);
+        }
+    }
+    let mut en_passant_files = [0; 8];
+    for key in en_passant_files.iter_mut() {
+        *key = rng.gen_range(0..u64::MAX);
+    }
+
     ZobristValues {
         white_pieces: white_zobrist_table,
         black_pieces: black_zobrist_table,
-        white_turn: rng.gen_range(0..u64::MAX),
+        castle_rights,
+        en_passant_files,
         black_turn: rng.gen_range(0..u64::MAX),
     }
 }
 
-fn hash(
-    board: &Board,
-    zobrist_values: &ZobristValues,
-) -> u64 {
-    let mut final_hash = 0;
-    let color_value = match board.side_to_move() {
-        Color::White => zobrist_values.white_turn,
-        Color::Black => zobrist_values.black_turn
-    };
-
-    for i in 0..64 {
-        if let Some(piece) = board.piece_on(unsafe { Square::new(i) }) {
-            if let Some(color) = board.color_on(unsafe { Square::new(i) }) {
-                match color {
-                    Color::White => {
-                        final_hash = final_hash ^ zobrist_values.white_pieces[i as usize][piece.to_index()] ^ color_value
-                    }
-                    Color::Black => {
-                        final_hash = final_hash ^ zobrist_values.black_pieces[i as usize][piece.to_index()] ^ color_value
-                    }
-                };
-            }
+#[cfg(test)]
+mod tests {
+    use super::ZobristValues;
+    use chess::{Board, ChessMove, MoveGen};
+    use std::str::FromStr;
+
+    /**
+     * Walks every line up to `depth` like perft does, checking that the incremental key
+     * always matches the key hashed from scratch.
+     */
+    fn walk(zobrist: &ZobristValues, board: &Board, hash: u64, depth: u8) -> u64 {
+        assert_eq!(hash, zobrist.hash(board), "{}", board);
+        if depth == 0 {
+            return 1;
         }
+
+        let mut nodes = 0;
+        for chess_move in MoveGen::new_legal(board) {
+            let new_board = board.make_move_new(chess_move);
+            let new_hash = zobrist.update_hash(hash, board, chess_move, &new_board);
+            nodes += walk(zobrist, &new_board, new_hash, depth - 1);
+        }
+        nodes
+    }
+
+    fn walk_from_fen(fen: &str, depth: u8) -> u64 {
+        let zobrist = ZobristValues::default();
+        let board = Board::from_str(fen).unwrap();
+        walk(&zobrist, &board, zobrist.hash(&board), depth)
+    }
+
+    #[test]
+    fn incremental_matches_full_hash_from_startpos() {
+        let nodes = walk_from_fen(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            4,
+        );
+        assert_eq!(nodes, 197281);
+    }
+
+    #[test]
+    fn incremental_matches_full_hash_with_castling_and_en_passant() {
+        // "kiwipete"
+        let nodes = walk_from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            3,
+        );
+        assert_eq!(nodes, 97862);
+    }
+
+    #[test]
+    fn incremental_matches_full_hash_with_promotions() {
+        let nodes = walk_from_fen(
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            3,
+        );
+        assert_eq!(nodes, 9467);
+    }
+
+    #[test]
+    fn keys_tell_apart_castle_rights_en_passant_and_side_to_move() {
+        let zobrist = ZobristValues::default();
+        let hash = |fen: &str| zobrist.hash(&Board::from_str(fen).unwrap());
+
+        let castling = hash("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        let no_castling = hash("r3k2r/8/8/8/8/8/8/R3K2R w - - 0 1");
+        assert_ne!(castling, no_castling);
+
+        let white_to_move = hash("4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+        let black_to_move = hash("4k3/8/8/8/8/8/8/4K3 b - - 0 1");
+        assert_ne!(white_to_move, black_to_move);
+
+        let en_passant = hash("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1");
+        let no_en_passant = hash("4k3/8/8/8/3pP3/8/8/4K3 b - - 0 1");
+        assert_ne!(en_passant, no_en_passant);
+
+        let king_e1 = hash("4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+        let king_d1 = hash("4k3/8/8/8/8/8/8/3K4 w - - 0 1");
+        assert_ne!(king_e1, king_d1);
+    }
+
+    #[test]
+    fn null_move_matches_full_hash() {
+        let zobrist = ZobristValues::default();
+        // the en passant square disappears with the null move.
+        let board = Board::from_str("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1").unwrap();
+        let new_board = board.null_move().unwrap();
+        assert_eq!(
+            zobrist.update_null_move(zobrist.hash(&board), &board, &new_board),
+            zobrist.hash(&new_board)
+        );
     }
 
-    final_hash
+    #[test]
+    fn transpositions_have_the_same_key() {
+        let zobrist = ZobristValues::default();
+        let play = |moves: &[&str]| {
+            let mut board = Board::default();
+            let mut hash = zobrist.hash(&board);
+            for chess_move in moves {
+                let chess_move = ChessMove::from_str(chess_move).unwrap();
+                let new_board = board.make_move_new(chess_move);
+                hash = zobrist.update_hash(hash, &board, chess_move, &new_board);
+                board = new_board;
+            }
+            hash
+        };
+
+        assert_eq!(
+            play(&["g1f3", "g8f6", "b1c3", "b8c6"]),
+            play(&["b1c3", "b8c6", "g1f3", "g8f6"])
+        );
+    }
 }
@@ -1 +1,2 @@
 pub mod pieces_colored;
+pub mod score;
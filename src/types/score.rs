@@ -0,0 +1,97 @@
+/**
+ * Scores are plain centipawns relative to the side to move,
+ * except near +-MATE where they encode the distance to a checkmate.
+ *
+ * Mate scores are relative to the root: being mated at ply 3 is `-MATE + 3`,
+ * so a shorter mate always scores better than a longer one.
+ */
+pub const INFINITY: i32 = 999999;
+pub const MATE: i32 = 100000;
+/**
+ * Any score beyond this is a mate score, leaves room for the ply of the mate.
+ */
+pub const MATE_BOUND: i32 = MATE - 1000;
+pub const DRAW: i32 = 0;
+
+pub fn mated_in(ply: usize) -> i32 {
+    -MATE + ply as i32
+}
+
+pub fn mate_in(ply: usize) -> i32 {
+    MATE - ply as i32
+}
+
+pub fn is_mate_score(score: i32) -> bool {
+    score.abs() >= MATE_BOUND
+}
+
+/**
+ * The tt is shared between nodes at different plies, so mate scores are stored
+ * relative to the node they were found at instead of the root.
+ */
+pub fn score_to_tt(score: i32, ply: usize) -> i32 {
+    if score >= MATE_BOUND {
+        score + ply as i32
+    } else if score <= -MATE_BOUND {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+pub fn score_from_tt(score: i32, ply: usize) -> i32 {
+    if score >= MATE_BOUND {
+        score - ply as i32
+    } else if score <= -MATE_BOUND {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+/**
+ * Moves (not plies) until mate, negative if the side to move is the one getting mated.
+ * None for centipawn scores.
+ */
+pub fn mate_distance(score: i32) -> Option<i32> {
+    if score >= MATE_BOUND {
+        Some((MATE - score + 1) / 2)
+    } else if score <= -MATE_BOUND {
+        Some(-(MATE + score) / 2)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_mate_score, mate_distance, mate_in, mated_in, score_from_tt, score_to_tt};
+
+    #[test]
+    fn counts_moves_to_mate() {
+        // our mate in n moves is delivered at ply 2n - 1, being mated in n moves happens at ply 2n.
+        for moves in 1..=3 {
+            assert_eq!(mate_distance(mate_in(2 * moves - 1)), Some(moves as i32));
+            assert_eq!(mate_distance(mated_in(2 * moves)), Some(-(moves as i32)));
+        }
+        assert_eq!(mate_distance(mated_in(0)), Some(0));
+        assert_eq!(mate_distance(250), None);
+        assert_eq!(mate_distance(-250), None);
+    }
+
+    #[test]
+    fn stores_mates_relative_to_the_node() {
+        for ply in [0, 1, 4, 20] {
+            for score in [mate_in(ply + 1), mate_in(ply + 5), mated_in(ply + 2), 35, -35, 0] {
+                let stored = score_to_tt(score, ply);
+                assert_eq!(score_from_tt(stored, ply), score);
+                assert_eq!(is_mate_score(stored), is_mate_score(score));
+            }
+        }
+        // a mate found 3 plies below the root, read again 1 ply below the root in another line.
+        let stored = score_to_tt(mate_in(5), 3);
+        assert_eq!(stored, mate_in(2));
+        assert_eq!(score_from_tt(stored, 1), mate_in(3));
+        assert_eq!(score_from_tt(score_to_tt(mated_in(6), 4), 2), mated_in(4));
+    }
+}
@@ -1,2 +1,4 @@
 pub mod conversion;
+pub mod options;
+#[allow(clippy::module_inception)]
 pub mod uci;
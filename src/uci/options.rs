@@ -0,0 +1,333 @@
+use std::fmt;
+
+use vampirc_uci::UciOptionConfig;
+
+use crate::bots::skill::{DEFAULT_ELO, MAX_ELO, MAX_SKILL_LEVEL, MIN_ELO};
+
+/**
+ * Transposition table size in MB.
+ */
+pub const DEFAULT_HASH_MB: usize = 64;
+pub const MAX_HASH_MB: usize = 32768;
+pub const MAX_THREADS: usize = 256;
+pub const MAX_MULTI_PV: usize = 256;
+/**
+ * Contempt in centipawns, both ways.
+ */
+pub const MAX_CONTEMPT: i32 = 100;
+
+/**
+ * Every option the engine understands.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EngineOption {
+    Hash,
+    ClearHash,
+    Threads,
+    MultiPv,
+    Ponder,
+    NullMove,
+    Contempt,
+    SkillLevel,
+    LimitStrength,
+    Elo,
+    Seed,
+}
+
+/**
+ * The type of an option and the values it allows, like the UCI "option" command describes them.
+ */
+#[derive(Clone, Copy, Debug)]
+pub enum OptionKind {
+    Check {
+        default: bool,
+    },
+    Spin {
+        default: i64,
+        min: i64,
+        max: i64,
+    },
+    Combo {
+        default: &'static str,
+        values: &'static [&'static str],
+    },
+    String {
+        default: &'static str,
+    },
+    Button,
+}
+
+/**
+ * A "setoption" value that was checked against its option's kind.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OptionValue {
+    Check(bool),
+    Spin(i64),
+    Combo(String),
+    String(String),
+    Button,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum OptionError {
+    UnknownOption(String),
+    MissingValue(&'static str),
+    InvalidValue(&'static str, String),
+}
+
+impl fmt::Display for OptionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OptionError::UnknownOption(name) => write!(f, "unknown option {}", name),
+            OptionError::MissingValue(name) => write!(f, "option {} needs a value", name),
+            OptionError::InvalidValue(name, value) => {
+                write!(f, "invalid value {} for option {}", value, name)
+            }
+        }
+    }
+}
+
+pub struct UciOption {
+    pub option: EngineOption,
+    pub name: &'static str,
+    pub kind: OptionKind,
+}
+
+/**
+ * The options advertised on "uci", in that order.
+ */
+pub const OPTIONS: [UciOption; 11] = [
+    UciOption {
+        option: EngineOption::Hash,
+        name: "Hash",
+        kind: OptionKind::Spin {
+            default: DEFAULT_HASH_MB as i64,
+            min: 1,
+            max: MAX_HASH_MB as i64,
+        },
+    },
+    UciOption {
+        option: EngineOption::ClearHash,
+        name: "Clear Hash",
+        kind: OptionKind::Button,
+    },
+    UciOption {
+        option: EngineOption::Threads,
+        name: "Threads",
+        kind: OptionKind::Spin {
+            default: 1,
+            min: 1,
+            max: MAX_THREADS as i64,
+        },
+    },
+    UciOption {
+        option: EngineOption::MultiPv,
+        name: "MultiPV",
+        kind: OptionKind::Spin {
+            default: 1,
+            min: 1,
+            max: MAX_MULTI_PV as i64,
+        },
+    },
+    UciOption {
+        option: EngineOption::Ponder,
+        name: "Ponder",
+        kind: OptionKind::Check { default: false },
+    },
+    UciOption {
+        option: EngineOption::NullMove,
+        name: "NullMove",
+        kind: OptionKind::Check { default: true },
+    },
+    UciOption {
+        option: EngineOption::Contempt,
+        name: "Contempt",
+        kind: OptionKind::Spin {
+            default: 0,
+            min: -MAX_CONTEMPT as i64,
+            max: MAX_CONTEMPT as i64,
+        },
+    },
+    UciOption {
+        option: EngineOption::SkillLevel,
+        name: "Skill Level",
+        kind: OptionKind::Spin {
+            default: MAX_SKILL_LEVEL as i64,
+            min: 0,
+            max: MAX_SKILL_LEVEL as i64,
+        },
+    },
+    UciOption {
+        option: EngineOption::LimitStrength,
+        name: "UCI_LimitStrength",
+        kind: OptionKind::Check { default: false },
+    },
+    UciOption {
+        option: EngineOption::Elo,
+        name: "UCI_Elo",
+        kind: OptionKind::Spin {
+            default: DEFAULT_ELO as i64,
+            min: MIN_ELO as i64,
+            max: MAX_ELO as i64,
+        },
+    },
+    // 0 is a new random seed every search.
+    UciOption {
+        option: EngineOption::Seed,
+        name: "Seed",
+        kind: OptionKind::Spin {
+            default: 0,
+            min: 0,
+            max: i32::MAX as i64,
+        },
+    },
+];
+
+impl UciOption {
+    /**
+     * Option names are case insensitive.
+     */
+    pub fn find(name: &str) -> Option<&'static UciOption> {
+        OPTIONS
+            .iter()
+            .find(|option| option.name.eq_ignore_ascii_case(name.trim()))
+    }
+
+    pub fn to_config(&self) -> UciOptionConfig {
+        let name = self.name.to_string();
+        match self.kind {
+            OptionKind::Check { default } => UciOptionConfig::Check {
+                name,
+                default: Some(default),
+            },
+            OptionKind::Spin { default, min, max } => UciOptionConfig::Spin {
+                name,
+                default: Some(default),
+                min: Some(min),
+                max: Some(max),
+            },
+            OptionKind::Combo { default, values } => UciOptionConfig::Combo {
+                name,
+                default: Some(default.to_string()),
+                var: values.iter().map(|value| value.to_string()).collect(),
+            },
+            OptionKind::String { default } => UciOptionConfig::String {
+                name,
+                default: Some(default.to_string()),
+            },
+            OptionKind::Button => UciOptionConfig::Button { name },
+        }
+    }
+
+    /**
+     * Checks the value of a "setoption" for this option, spin values outside of the range are rejected.
+     */
+    pub fn parse(&self, value: Option<&str>) -> Result<OptionValue, OptionError> {
+        if let OptionKind::Button = self.kind {
+            return Ok(OptionValue::Button);
+        }
+        let value = value
+            .map(str::trim)
+            .ok_or(OptionError::MissingValue(self.name))?;
+        let invalid = || OptionError::InvalidValue(self.name, value.to_string());
+
+        match self.kind {
+            OptionKind::Check { .. } => match value.to_lowercase().as_str() {
+                "true" => Ok(OptionValue::Check(true)),
+                "false" => Ok(OptionValue::Check(false)),
+                _ => Err(invalid()),
+            },
+            OptionKind::Spin { min, max, .. } => match value.parse::<i64>() {
+                Ok(number) if (min..=max).contains(&number) => Ok(OptionValue::Spin(number)),
+                _ => Err(invalid()),
+            },
+            OptionKind::Combo { values, .. } => values
+                .iter()
+                .find(|allowed| allowed.eq_ignore_ascii_case(value))
+                .map(|allowed| OptionValue::Combo(allowed.to_string()))
+                .ok_or_else(invalid),
+            // the GUI sends "<empty>" for an empty string.
+            OptionKind::String { .. } if value == "<empty>" => Ok(OptionValue::String(String::new())),
+            OptionKind::String { .. } => Ok(OptionValue::String(value.to_string())),
+            OptionKind::Button => Ok(OptionValue::Button),
+        }
+    }
+}
+
+/**
+ * Finds the option of a "setoption" and checks its value.
+ */
+pub fn parse_set_option(
+    name: &str,
+    value: Option<&str>,
+) -> Result<(EngineOption, OptionValue), OptionError> {
+    let option =
+        UciOption::find(name).ok_or_else(|| OptionError::UnknownOption(name.to_string()))?;
+    Ok((option.option, option.parse(value)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_set_option, EngineOption, OptionError, OptionKind, OptionValue, UciOption};
+
+    #[test]
+    fn finds_options_by_any_case() {
+        assert_eq!(
+            parse_set_option("hash", Some("128")),
+            Ok((EngineOption::Hash, OptionValue::Spin(128)))
+        );
+        assert_eq!(
+            parse_set_option("clear hash", None),
+            Ok((EngineOption::ClearHash, OptionValue::Button))
+        );
+        assert_eq!(
+            parse_set_option("Style", Some("Risky")),
+            Err(OptionError::UnknownOption("Style".to_string()))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        assert_eq!(
+            parse_set_option("Threads", Some("0")),
+            Err(OptionError::InvalidValue("Threads", "0".to_string()))
+        );
+        assert!(parse_set_option("Hash", Some("lots")).is_err());
+        assert_eq!(
+            parse_set_option("NullMove", None),
+            Err(OptionError::MissingValue("NullMove"))
+        );
+        assert_eq!(
+            parse_set_option("NullMove", Some("FALSE")),
+            Ok((EngineOption::NullMove, OptionValue::Check(false)))
+        );
+        assert_eq!(
+            parse_set_option("Contempt", Some("-20")),
+            Ok((EngineOption::Contempt, OptionValue::Spin(-20)))
+        );
+        assert!(parse_set_option("Contempt", Some("1000")).is_err());
+    }
+
+    #[test]
+    fn checks_combo_and_string_values() {
+        let combo = UciOption {
+            option: EngineOption::NullMove,
+            name: "Style",
+            kind: OptionKind::Combo {
+                default: "Normal",
+                values: &["Solid", "Normal", "Risky"],
+            },
+        };
+        assert_eq!(combo.parse(Some("risky")), Ok(OptionValue::Combo("Risky".to_string())));
+        assert!(combo.parse(Some("Reckless")).is_err());
+
+        let string = UciOption {
+            option: EngineOption::NullMove,
+            name: "Book",
+            kind: OptionKind::String { default: "" },
+        };
+        assert_eq!(string.parse(Some("<empty>")), Ok(OptionValue::String(String::new())));
+        assert_eq!(string.parse(Some("book.bin")), Ok(OptionValue::String("book.bin".to_string())));
+    }
+}
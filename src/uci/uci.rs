@@ -1,57 +1,171 @@
 use chess::ChessMove;
+use vampirc_uci::{UciInfoAttribute, UciMessage};
 
+use crate::tables::transposition::Bound;
+use crate::types::score;
+use crate::uci::conversion::chess_move_to_uci_move;
+
+#[derive(Default)]
 pub struct Uci {
     pub depth_data: Vec<DepthData>,
+    /**
+     * Every line (best first) of the last finished iteration, more than one with MultiPV.
+     */
+    pub lines: Vec<DepthData>,
     pub nodes_total: u64,
     pub ms_passed: u64,
+    /**
+     * Don't print `info` lines, for searches nobody is watching (like the bench).
+     */
+    pub quiet: bool,
 }
 
+/**
+ * The result of one finished iteration of the iterative deepening loop.
+ */
 #[derive(Clone, Debug)]
 pub struct DepthData {
     pub depth: u16,
+    /**
+     * Which of the best lines this is, starting at 1.
+     */
+    pub multi_pv: usize,
+    pub eval: i32,
     pub best_move: Option<ChessMove>,
-    pub node_count: u32,
+    /**
+     * The principal variation, the line both sides are expected to play. Starts with the best move.
+     */
+    pub pv: Vec<ChessMove>,
+    /**
+     * Whether `eval` is exact or only a bound, if the search window was too narrow.
+     */
+    pub bound: Bound,
+    /**
+     * Nodes searched so far, by every thread.
+     */
+    pub node_count: u64,
+    pub ms_passed: u64,
+    /**
+     * How full the transposition table is, in permill.
+     */
+    pub hashfull: u16,
 }
 
-impl Default for Uci {
-    fn default() -> Uci {
-        Uci {
-            nodes_total: 0,
-            ms_passed: 0,
-            depth_data: vec![],
+impl Uci {
+    pub fn set_ms_passed(&mut self, ms_passed: u64) {
+        self.ms_passed = ms_passed;
+    }
+
+    pub fn get_depth_data(&self) -> &Vec<DepthData> {
+        &self.depth_data
+    }
+
+    /**
+     * Stores the data of a finished iteration and prints it as an `info` line.
+     */
+    pub fn report_depth(&mut self, data: DepthData) {
+        self.print_info(&data);
+        self.depth_data.push(data);
+    }
+
+    /**
+     * Prints an `info` line without storing the data, for a search that fell outside
+     * its aspiration window and is searched again.
+     */
+    pub fn print_info(&self, data: &DepthData) {
+        if !self.quiet {
+            println!("{}", UciMessage::Info(data.to_info_attributes()));
         }
     }
 }
 
-impl Uci {
-    pub fn get_nodes_per_second(&self) -> f64 {
-        self.nodes_total as f64 / (self.ms_passed as f64 / 1000.0)
+impl DepthData {
+    pub fn to_info_attributes(&self) -> Vec<UciInfoAttribute> {
+        let mut info_vec = vec![
+            UciInfoAttribute::Depth(self.depth as u8),
+            UciInfoAttribute::MultiPv(self.multi_pv as u16),
+            score_to_uci(self.eval, self.bound),
+            UciInfoAttribute::Nodes(self.node_count),
+            UciInfoAttribute::Nps(self.nodes_per_second()),
+            UciInfoAttribute::HashFull(self.hashfull),
+            UciInfoAttribute::Time(vampirc_uci::Duration::milliseconds(self.ms_passed as i64)),
+        ];
+        if !self.pv.is_empty() {
+            let pv = self.pv.iter().map(chess_move_to_uci_move).collect();
+            info_vec.push(UciInfoAttribute::Pv(pv));
+        }
+        info_vec
     }
 
-    pub fn set_ms_passed(&mut self, ms_passed: u64) {
-        self.ms_passed = ms_passed;
+    /**
+     * The first iterations can take less than a millisecond, they count as one.
+     */
+    pub fn nodes_per_second(&self) -> u64 {
+        self.node_count * 1000 / self.ms_passed.max(1)
     }
 
-    pub fn get_depth_data(&self) -> &Vec<DepthData> {
-        &self.depth_data
+    /**
+     * The reply we expect from the opponent, the second move of the pv.
+     */
+    pub fn ponder_move(&self) -> Option<ChessMove> {
+        self.pv.get(1).copied()
+    }
+}
+
+/**
+ * "score cp x" or "score mate x", from the side to move's point of view.
+ */
+pub fn score_to_uci(eval: i32, bound: Bound) -> UciInfoAttribute {
+    let (cp, mate) = match score::mate_distance(eval) {
+        Some(moves) => (None, Some(moves.clamp(i8::MIN as i32, i8::MAX as i32) as i8)),
+        None => (Some(eval), None),
+    };
+    UciInfoAttribute::Score {
+        cp,
+        mate,
+        lower_bound: (bound == Bound::Lower).then_some(true),
+        upper_bound: (bound == Bound::Upper).then_some(true),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::score_to_uci;
+    use crate::tables::transposition::Bound;
+    use crate::types::score;
+    use vampirc_uci::UciInfoAttribute;
+
+    fn uci_score(cp: Option<i32>, mate: Option<i8>) -> UciInfoAttribute {
+        UciInfoAttribute::Score {
+            cp,
+            mate,
+            lower_bound: None,
+            upper_bound: None,
+        }
     }
 
-    pub fn update_depth_data(&mut self, depth: u16, max_depth: u16, best_move: Option<ChessMove>) {
-        let reversed_depth = max_depth + 1 - depth;
-
-        if let Some(data) = self
-            .depth_data
-            .iter_mut()
-            .find(|d| d.depth == reversed_depth)
-        {
-            data.best_move = best_move;
-            data.node_count += 1;
-        } else {
-            self.depth_data.push(DepthData {
-                depth: reversed_depth,
-                best_move,
-                node_count: 1,
-            });
+    #[test]
+    fn reports_mates_in_moves() {
+        for moves in 1..=3 {
+            let ply = 2 * moves as usize;
+            assert_eq!(
+                score_to_uci(score::mate_in(ply - 1), Bound::Exact),
+                uci_score(None, Some(moves))
+            );
+            assert_eq!(
+                score_to_uci(score::mated_in(ply), Bound::Exact),
+                uci_score(None, Some(-moves))
+            );
         }
+        assert_eq!(score_to_uci(-42, Bound::Exact), uci_score(Some(-42), None));
+        assert_eq!(
+            score_to_uci(42, Bound::Lower),
+            UciInfoAttribute::Score {
+                cp: Some(42),
+                mate: None,
+                lower_bound: Some(true),
+                upper_bound: None,
+            }
+        );
     }
 }